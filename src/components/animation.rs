@@ -0,0 +1,65 @@
+//! Component for characters that animate depending on where they're going
+use crate::util::data::{CharacterAnimationConfig, Direction};
+
+use amethyst::ecs::{Component, DenseVecStorage};
+
+use std::time::Duration;
+
+pub struct CharacterAnimation {
+    pub config: CharacterAnimationConfig,
+    /// The direction we're looking in. This stays the same when we stop, so we idle facing the
+    /// way we were last moving.
+    pub facing: Direction,
+    walking: bool,
+    /// Time spent in the current animation, used to pick a frame
+    elapsed: Duration,
+}
+
+impl Component for CharacterAnimation {
+    type Storage = DenseVecStorage<Self>;
+}
+
+impl CharacterAnimation {
+    pub fn new(config: CharacterAnimationConfig) -> Self {
+        Self {
+            config,
+            facing: Direction::South,
+            walking: false,
+            elapsed: Duration::default(),
+        }
+    }
+
+    /// Advance the animation and return the sprite to show, if there's one for our facing.
+    ///
+    /// * `direction` - The direction we're currently moving in, or `None` if standing still
+    /// * `delta` - Time since the last update
+    pub fn update(&mut self, direction: Option<Direction>, delta: Duration) -> Option<usize> {
+        let walking = direction.is_some();
+        let facing = direction.unwrap_or(self.facing);
+
+        // Start the new animation from its first frame
+        if walking != self.walking || facing != self.facing {
+            self.elapsed = Duration::default();
+        } else {
+            self.elapsed += delta;
+        }
+        self.walking = walking;
+        self.facing = facing;
+
+        let animations = if walking {
+            &self.config.walk
+        } else {
+            &self.config.idle
+        };
+        let frames = animations
+            .get(&facing)
+            .or_else(|| animations.get(&facing.cardinal()))?;
+        if frames.is_empty() {
+            return None;
+        }
+
+        let frame_duration = self.config.frame_duration.max(1) as u128;
+        let frame = (self.elapsed.as_millis() / frame_duration) as usize % frames.len();
+        Some(frames[frame])
+    }
+}
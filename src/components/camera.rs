@@ -0,0 +1,84 @@
+//! Component for cameras that follow an entity around
+use crate::util::chunk::Rect;
+
+use amethyst::ecs::{Component, DenseVecStorage, Entity};
+
+pub struct CameraFollow {
+    /// The entity to keep in view
+    pub target: Entity,
+    /// Half the width and height of the area around the view's center that the target can move
+    /// in without the camera following
+    pub deadzone: (f32, f32),
+    /// How quickly the camera catches up, per second. Zero snaps straight to the goal.
+    pub smoothing: f32,
+    /// Seconds of the target's velocity to look ahead by, so we see more of where it's going
+    pub look_ahead: f32,
+}
+
+impl Component for CameraFollow {
+    type Storage = DenseVecStorage<Self>;
+}
+
+impl CameraFollow {
+    /// Where the view's center should move to this frame
+    ///
+    /// * `center` - the view's current center
+    /// * `target` - position of the followed entity
+    /// * `velocity` - velocity of the followed entity, in pixels per second
+    /// * `delta` - seconds since the last frame
+    pub fn next_center(
+        &self,
+        center: (f32, f32),
+        target: (f32, f32),
+        velocity: (f32, f32),
+        delta: f32,
+    ) -> (f32, f32) {
+        let goal = (
+            target.0 + velocity.0 * self.look_ahead,
+            target.1 + velocity.1 * self.look_ahead,
+        );
+        // Only move far enough to bring the goal back to the edge of the deadzone
+        let goal = (
+            out_of_deadzone(center.0, goal.0, self.deadzone.0),
+            out_of_deadzone(center.1, goal.1, self.deadzone.1),
+        );
+        if self.smoothing <= 0.0 {
+            return goal;
+        }
+        // Exponential smoothing, so the result doesn't depend on the frame rate
+        let t = 1.0 - (-self.smoothing * delta).exp();
+        (
+            center.0 + (goal.0 - center.0) * t,
+            center.1 + (goal.1 - center.1) * t,
+        )
+    }
+}
+
+/// Keep a view's center far enough inside `bounds` that the view never shows past its edges. If
+/// the bounds are smaller than the view along an axis, the view is centered on them instead.
+///
+/// * `half_size` - half the width and height of the view
+pub fn clamp_center(center: (f32, f32), half_size: (f32, f32), bounds: &Rect) -> (f32, f32) {
+    (
+        clamp_axis(center.0, half_size.0, bounds.left, bounds.right),
+        clamp_axis(center.1, half_size.1, bounds.bottom, bounds.top),
+    )
+}
+
+fn clamp_axis(center: f32, half_size: f32, min: f32, max: f32) -> f32 {
+    if max - min <= half_size * 2.0 {
+        (min + max) / 2.0
+    } else {
+        center.max(min + half_size).min(max - half_size)
+    }
+}
+
+fn out_of_deadzone(center: f32, goal: f32, deadzone: f32) -> f32 {
+    if goal - center > deadzone {
+        goal - deadzone
+    } else if center - goal > deadzone {
+        goal + deadzone
+    } else {
+        center
+    }
+}
@@ -0,0 +1,12 @@
+use amethyst::ecs::{Component, DenseVecStorage};
+
+/// Sprites with this are drawn in front of or behind each other depending on how far down the
+/// map their feet are, so characters can walk behind and in front of props
+pub struct DepthSort {
+    /// How far below the transform the sprite's feet are
+    pub foot_offset: f32,
+}
+
+impl Component for DepthSort {
+    type Storage = DenseVecStorage<Self>;
+}
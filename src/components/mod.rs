@@ -1,5 +1,15 @@
+mod animation;
+mod camera;
+mod depth;
 mod movement;
+mod object;
 mod player;
+mod tile;
 
-pub use movement::{GridMovement, Movement};
+pub use animation::CharacterAnimation;
+pub use camera::{clamp_center, CameraFollow};
+pub use depth::DepthSort;
+pub use movement::{GridMovement, Movement, SnapMode};
+pub use object::{Npc, Trigger, Warp};
 pub use player::Player;
+pub use tile::{AnimatedTile, MapEntity, ParallaxLayer, TileChunk};
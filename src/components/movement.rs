@@ -1,4 +1,5 @@
 //! Component for+ axis-moving players and AI
+use crate::util::{collision::CollisionGrid, data::MAP_ORIGIN};
 use amethyst::{
     core::{
         math::Unit,
@@ -9,13 +10,21 @@ use amethyst::{
     ecs::{Component, DenseVecStorage},
 };
 
+use serde::{Deserialize, Serialize};
 use std::time::Duration;
 
 pub struct GridMovement {
     /// Milliseconds taken to move to one position
     pub duration: Duration,
-    /// Pixel cube size of grid when moving one step
-    pub size: Float,
+    /// Milliseconds taken to move to one position while running
+    pub run_duration: Duration,
+    /// Whether steps started from now on use `run_duration`
+    pub running: bool,
+    /// The duration of the step we're currently taking. Fixed when the step starts, so that
+    /// starting or stopping a run halfway doesn't make us jump.
+    step_duration: Duration,
+    /// Pixel width and height of a grid cell, which is how far one step moves us
+    pub size: (Float, Float),
     /// To save us from calculating every frame, this field is calculated when we get a new
     /// direction when the movement is not busy
     pub target: Vector3<Float>,
@@ -37,13 +46,18 @@ impl GridMovement {
     ///
     /// * `translation` - the initial starting point (same as the transform) for this component.
     /// This is useful so we can position objects on an absolute grid and later compare between them
-    pub fn new(_translation: Vector3<Float>, size: Float) -> Self {
+    /// * `size` - width and height of a grid cell, usually the map's tile size
+    pub fn new(translation: Vector3<Float>, size: (Float, Float)) -> Self {
         Self {
             duration: Duration::from_millis(200u64),
+            run_duration: Duration::from_millis(120u64),
+            running: false,
+            step_duration: Duration::from_millis(200u64),
             size,
-            target: Vector3::zeros(),
+            // We start out resting on our spawn point, so the first move can begin right away
+            target: translation,
             target_local: Vector3::zeros(),
-            start: Vector3::zeros(),
+            start: translation,
             start_time: Duration::default(),
         }
     }
@@ -51,6 +65,10 @@ impl GridMovement {
         self.duration = duration;
         self
     }
+    pub fn set_running(&mut self, running: bool) -> &Self {
+        self.running = running;
+        self
+    }
     /// Set the grid-aligned target using a unit vector. Under the hood, we take this unit vector and create
     /// a target coordinate to lerp to.
     ///
@@ -58,26 +76,46 @@ impl GridMovement {
     /// integer values.
     /// * `transform` - The associated transform of this component's entity. We use it to read our
     /// current transforms so we don't need to keep a copy of the data.
+    /// * `collision` - Moves that would end inside a solid cell are refused.
     pub fn set_move(
         &mut self,
         direction: &Unit<Vector3<Float>>,
         transform: &Transform,
         time: Duration,
+        collision: &CollisionGrid,
     ) -> &Self {
         // If our current transform position is the same as our target position, that means we are
         // free to start another movement along the grid.
-        if transform.translation() == &self.target {
+        if !self.is_busy(transform) {
+            let direction = direction.into_inner();
+            let target_local = Vector3::new(direction.x * self.size.0, direction.y * self.size.1, Float::from(0.));
+            let target = transform.translation() + target_local;
+            if collision.is_point_blocked(target.x.as_f32(), target.y.as_f32()) {
+                return self;
+            }
             // Replace our start and target positions
             self.start = transform.translation().clone();
-            self.target_local = direction.into_inner() * self.size;
-            self.target = self.start + self.target_local;
+            self.target_local = target_local;
+            self.target = target;
             // Update the time at which we started this movement
             self.start_time = time;
+            self.step_duration = if self.running {
+                self.run_duration
+            } else {
+                self.duration
+            };
         }
         self
     }
 
-    pub fn set_size(&mut self, size: Float) -> &Self {
+    /// Whether we are still on our way to the current target. New moves are ignored until this
+    /// returns false. Only x and y count, since depth sorting is free to change z.
+    pub fn is_busy(&self, transform: &Transform) -> bool {
+        let translation = transform.translation();
+        translation.x != self.target.x || translation.y != self.target.y
+    }
+
+    pub fn set_size(&mut self, size: (Float, Float)) -> &Self {
         self.size = size;
         self
     }
@@ -87,11 +125,11 @@ impl GridMovement {
         let difference = current_time - self.start_time;
         // cap value if too high. Difference should always be less than or equal to duration. If
         // not, we can just return 1.
-        if !(difference <= self.duration) {
+        if !(difference <= self.step_duration) {
             return Float::from(1.);
         }
         // normalize and return
-        Float::from(difference.div_duration_f64(self.duration))
+        Float::from(difference.div_duration_f64(self.step_duration))
     }
 }
 
@@ -101,52 +139,409 @@ impl GridMovement {
 /// * `Nearest` - snap to the nearest grid point, even if it is "backwards". Can cause "rubber band"
 /// type look.
 /// * `Continuation` - finish any movement started, even if it is not the nearest snap point
-#[allow(dead_code)]
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
 pub enum SnapMode {
     None,
     Nearest,
     Continuation,
 }
 
+impl Default for SnapMode {
+    fn default() -> Self {
+        SnapMode::None
+    }
+}
+
 /// Instead of calculating the next transform from a block over, `FreeMovement`
 /// will continuously move in a direction, and then has the option to align to the nearest grid
 /// while maintaining speed when the movement ends. This is preferred with players or AI that
 /// move smoothly, or need to not move faster on diagonals.
+///
+/// Rather than jumping straight to full speed, we accelerate towards `max_speed` while a
+/// direction is set and slow down again once it's released.
 pub struct Movement {
-    /// Move this many pixels, times the `size` per second
-    pub speed: Float,
+    /// Fastest we can move, in pixels per second
+    pub max_speed: Float,
+    /// How quickly we speed up towards `max_speed`, in pixels per second squared
+    pub acceleration: Float,
+    /// How quickly we slow down once there's no direction, in pixels per second squared
+    pub deceleration: Float,
+    /// `max_speed` is multiplied by this while running
+    pub run_multiplier: Float,
+    pub running: bool,
     /// Pixel cube size of grid. This is used to calculate speed, so it must be set even if grid
     /// snapping is disabled.
     pub size: Float,
+    /// World position of the top left corner of grid cell (0, 0). Snapping stops us in the
+    /// middle of a cell, the same place tiles are drawn.
+    pub origin: (Float, Float),
+    /// Half the width and height of the box we collide with, centered on us. It's smaller than
+    /// a grid cell, so we fit through gaps one cell wide without having to line up exactly.
+    pub feet: (Float, Float),
+    /// Always either normalised or zero, so diagonals aren't faster than straight lines
     direction: Unit<Vector3<Float>>,
+    /// How far towards `max_speed` we're being pushed, from 0 to 1. Analog sticks that are only
+    /// tilted part way move us slower.
+    throttle: Float,
+    /// Current speed and heading, in pixels per second
+    velocity: Vector3<Float>,
     pub snap_mode: SnapMode,
+    /// The last non-zero direction we moved in. `Continuation` snapping needs to know which way
+    /// we were heading after the direction has already been zeroed.
+    heading: Vector3<Float>,
+    /// The grid point we are gliding towards once the direction goes to zero. This is computed
+    /// once when the movement stops, so floating point error can't push us a whole cell further.
+    snap_target: Option<Vector3<Float>>,
+    /// How fast we glide to `snap_target`, which is the speed we had when the movement stopped
+    snap_speed: Float,
 }
 
 impl Default for Movement {
     fn default() -> Self {
         Self {
-            speed: Float::from(128.),
+            max_speed: Float::from(128.),
+            acceleration: Float::from(1024.),
+            deceleration: Float::from(1024.),
+            run_multiplier: Float::from(1.75),
+            running: false,
             size: Float::from(32.),
+            origin: (Float::from(MAP_ORIGIN.0), Float::from(MAP_ORIGIN.1)),
+            feet: (Float::from(12.), Float::from(12.)),
             direction: Unit::new_unchecked(Vector3::zeros()),
+            throttle: Float::from(0.),
+            velocity: Vector3::zeros(),
             snap_mode: SnapMode::None,
+            heading: Vector3::zeros(),
+            snap_target: None,
+            snap_speed: Float::from(0.),
         }
     }
 }
 
 impl Movement {
-    pub fn set_direction(&mut self, direction: Unit<Vector3<Float>>) -> &Self {
+    pub fn new(max_speed: Float, acceleration: Float, deceleration: Float) -> Self {
+        Self {
+            max_speed,
+            acceleration,
+            deceleration,
+            ..Self::default()
+        }
+    }
+
+    /// Set the direction to move in. The direction gets normalised here, and its length, capped
+    /// at 1, sets how close to `max_speed` we go. Diagonal key presses with a length over 1 are
+    /// no faster than straight ones.
+    pub fn set_direction(&mut self, direction: Vector3<Float>) -> &Self {
         // If direction is ever zero, we will need to call a special method to stop on the grid
-        self.direction = direction;
+        if direction == Vector3::zeros() {
+            self.direction = Unit::new_unchecked(direction);
+            self.throttle = Float::from(0.);
+        } else {
+            let (direction, length) = Unit::new_and_get(direction);
+            self.direction = direction;
+            self.throttle = length.min(Float::from(1.));
+        }
         self
     }
 
+    pub fn set_running(&mut self, running: bool) -> &Self {
+        self.running = running;
+        self
+    }
+
+    /// Current speed and heading, in pixels per second
+    pub fn velocity(&self) -> &Vector3<Float> {
+        &self.velocity
+    }
+
+    pub fn direction(&self) -> &Unit<Vector3<Float>> {
+        &self.direction
+    }
+
+    pub fn set_snap_mode(&mut self, snap_mode: SnapMode) -> &Self {
+        self.snap_mode = snap_mode;
+        self
+    }
+
+    /// Whether we are currently being pushed in a direction, as opposed to standing still or
+    /// gliding to a snap point
+    pub fn is_moving(&self) -> bool {
+        self.direction.into_inner() != Vector3::zeros()
+    }
+
     /// Get the transform to which to append to the current transform
-    pub fn next(&self, time: &Time) -> Vector3<Float> {
-        let scalar = Float::from(time.delta_seconds()) * self.speed;
-        self.direction.scale(scalar)
+    ///
+    /// * `translation` - the current translation of this component's entity. It is only read when
+    /// snapping, so we know how far we are from the grid.
+    /// * `time` - used to scale our speed by the frame delta
+    pub fn next(&mut self, translation: &Vector3<Float>, time: &Time) -> Vector3<Float> {
+        let delta = Float::from(time.delta_seconds());
+
+        if self.is_moving() {
+            // Any input cancels a pending snap, we'll compute a new one when we stop again
+            self.heading = self.direction.into_inner();
+            self.snap_target = None;
+            let mut speed = self.max_speed * self.throttle;
+            if self.running {
+                speed *= self.run_multiplier;
+            }
+            self.velocity = approach(
+                &self.velocity,
+                &self.direction.scale(speed),
+                self.acceleration * delta,
+            );
+            return self.velocity.scale(delta);
+        }
+
+        let target = match self.snap_target {
+            Some(target) => target,
+            None => {
+                let target = match self.snap_mode {
+                    SnapMode::None => {
+                        // Nothing to snap to, so just let friction bring us to a stop
+                        self.velocity = approach(
+                            &self.velocity,
+                            &Vector3::zeros(),
+                            self.deceleration * delta,
+                        );
+                        return self.velocity.scale(delta);
+                    }
+                    SnapMode::Nearest => self.nearest_point(translation),
+                    SnapMode::Continuation => self.continuation_point(translation),
+                };
+                self.snap_target = Some(target);
+                // Keep going as fast as we were. If we were already standing still somewhere
+                // off the grid, there's no speed to keep, so we use our usual one.
+                self.snap_speed = self.velocity.norm();
+                if self.snap_speed == Float::from(0.) {
+                    self.snap_speed = self.max_speed;
+                }
+                target
+            }
+        };
+
+        // Glide towards the snap point at the speed we stopped at, without overshooting it.
+        // Snapping is meant to feel like finishing the step, so it doesn't ease out.
+        let scalar = delta * self.snap_speed;
+        let remaining = target - translation;
+        let distance = remaining.norm();
+        if distance <= scalar {
+            self.snap_target = None;
+            self.velocity = Vector3::zeros();
+            remaining
+        } else {
+            self.velocity = remaining.scale(self.snap_speed / distance);
+            remaining.scale(scalar / distance)
+        }
+    }
+
+    /// The closest grid point to `translation`, even if it lies behind us. Only the x and y axes
+    /// are snapped, z is used for depth.
+    pub fn nearest_point(&self, translation: &Vector3<Float>) -> Vector3<Float> {
+        Vector3::new(
+            self.snap_axis(translation.x, self.origin.0, |cells| cells.round()),
+            self.snap_axis(translation.y, self.origin.1, |cells| cells.round()),
+            translation.z,
+        )
+    }
+
+    /// The next grid point along our last heading. Axes we weren't moving along snap to their
+    /// nearest point instead.
+    pub fn continuation_point(&self, translation: &Vector3<Float>) -> Vector3<Float> {
+        let snap = |position: Float, origin: Float, heading: Float| {
+            if heading > Float::from(0.) {
+                self.snap_axis(position, origin, |cells| cells.ceil())
+            } else if heading < Float::from(0.) {
+                self.snap_axis(position, origin, |cells| cells.floor())
+            } else {
+                self.snap_axis(position, origin, |cells| cells.round())
+            }
+        };
+        Vector3::new(
+            snap(translation.x, self.origin.0, self.heading.x),
+            snap(translation.y, self.origin.1, self.heading.y),
+            translation.z,
+        )
+    }
+
+    /// Snap `position` to a cell centre along one axis. Cell centres sit half a cell from the
+    /// origin and then every `size` from there, in either direction.
+    ///
+    /// * `to_cell` - rounds a position measured in cells to a whole cell
+    fn snap_axis(&self, position: Float, origin: Float, to_cell: fn(Float) -> Float) -> Float {
+        let first_center = origin + self.size / Float::from(2.);
+        to_cell((position - first_center) / self.size) * self.size + first_center
     }
 }
 
 impl Component for Movement {
     type Storage = DenseVecStorage<Self>;
 }
+
+/// Move `current` towards `target` by at most `max_delta`, without overshooting
+fn approach(current: &Vector3<Float>, target: &Vector3<Float>, max_delta: Float) -> Vector3<Float> {
+    let difference = target - current;
+    let distance = difference.norm();
+    if distance <= max_delta {
+        target.clone()
+    } else {
+        current + difference.scale(max_delta / distance)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A time resource where every frame takes exactly a 60th of a second
+    fn fixed_time() -> Time {
+        let mut time = Time::default();
+        time.set_delta_seconds(1.0 / 60.0);
+        time
+    }
+
+    fn point(x: f32, y: f32) -> Vector3<Float> {
+        Vector3::new(Float::from(x), Float::from(y), Float::from(0.))
+    }
+
+    fn assert_close(actual: &Vector3<Float>, expected: &Vector3<Float>) {
+        assert!(
+            (actual - expected).norm() < Float::from(0.001),
+            "expected {:?}, got {:?}",
+            expected,
+            actual
+        );
+    }
+
+    /// Hold right for `frames` starting from the tile centre at (16, -15), then let go and step
+    /// until we come to rest. Returns where we stopped.
+    fn move_right_and_release(snap_mode: SnapMode, frames: usize) -> Vector3<Float> {
+        let time = fixed_time();
+        let mut movement = Movement::default();
+        movement.set_snap_mode(snap_mode);
+        let mut translation = point(16., -15.);
+
+        movement.set_direction(point(1., 0.));
+        for _ in 0..frames {
+            translation += movement.next(&translation, &time);
+        }
+        movement.set_direction(Vector3::zeros());
+        for _ in 0..600 {
+            translation += movement.next(&translation, &time);
+            if movement.velocity() == &Vector3::zeros() {
+                break;
+            }
+        }
+        assert_eq!(movement.velocity(), &Vector3::zeros(), "never came to rest");
+        translation
+    }
+
+    #[test]
+    fn grid_steps_are_one_cell_of_the_grid() {
+        // Cells 16 wide and 12 high, starting in the middle of cell (0, 0)
+        let grid = CollisionGrid::new(4, 4, (16., 12.), MAP_ORIGIN);
+        let start = point(8., -5.);
+        let mut movement = GridMovement::new(start, (Float::from(16.), Float::from(12.)));
+        let transform = Transform::from(start);
+
+        movement.set_move(&Unit::new_unchecked(point(1., 0.)), &transform, Duration::default(), &grid);
+        assert_close(&movement.target, &point(24., -5.));
+
+        let mut movement = GridMovement::new(start, (Float::from(16.), Float::from(12.)));
+        movement.set_move(&Unit::new_unchecked(point(0., -1.)), &transform, Duration::default(), &grid);
+        assert_close(&movement.target, &point(8., -17.));
+    }
+
+    #[test]
+    fn grid_movers_ignore_depth_when_checking_if_they_arrived() {
+        let grid = CollisionGrid::new(4, 4, (32., 32.), MAP_ORIGIN);
+        let start = point(16., -15.);
+        let mut movement = GridMovement::new(start, (Float::from(32.), Float::from(32.)));
+        // Depth sorting moved us along z since we arrived
+        let transform = Transform::from(Vector3::new(start.x, start.y, Float::from(0.4)));
+        assert!(!movement.is_busy(&transform));
+
+        movement.set_move(&Unit::new_unchecked(point(1., 0.)), &transform, Duration::default(), &grid);
+        assert_eq!((movement.target.x.as_f32(), movement.target.y.as_f32()), (48., -15.));
+        assert!(movement.is_busy(&transform));
+    }
+
+    #[test]
+    fn grid_steps_into_solid_cells_are_refused() {
+        let mut grid = CollisionGrid::new(4, 4, (32., 32.), MAP_ORIGIN);
+        grid.set_solid(1, 0, true);
+        let start = point(16., -15.);
+        let mut movement = GridMovement::new(start, (Float::from(32.), Float::from(32.)));
+        let transform = Transform::from(start);
+        movement.set_move(&Unit::new_unchecked(point(1., 0.)), &transform, Duration::default(), &grid);
+        assert_close(&movement.target, &start);
+        assert!(!movement.is_busy(&transform));
+    }
+
+    #[test]
+    fn nearest_point_is_a_tile_centre() {
+        let movement = Movement::default();
+        assert_close(&movement.nearest_point(&point(20., -20.)), &point(16., -15.));
+        assert_close(&movement.nearest_point(&point(40., -40.)), &point(48., -47.));
+        assert_close(&movement.nearest_point(&point(-20., 20.)), &point(-16., 17.));
+    }
+
+    #[test]
+    fn continuation_point_is_the_next_tile_centre_along_the_heading() {
+        let mut movement = Movement::default();
+        movement.heading = point(1., 0.);
+        assert_close(&movement.continuation_point(&point(20., -20.)), &point(48., -15.));
+        movement.heading = point(0., -1.);
+        assert_close(&movement.continuation_point(&point(20., -20.)), &point(16., -47.));
+    }
+
+    #[test]
+    fn nearest_snap_glides_back_to_the_closest_tile() {
+        // Not even half a tile along, so the closest tile is the one we started on
+        let rest = move_right_and_release(SnapMode::Nearest, 6);
+        assert_close(&rest, &point(16., -15.));
+    }
+
+    #[test]
+    fn continuation_snap_finishes_the_step() {
+        let rest = move_right_and_release(SnapMode::Continuation, 6);
+        assert_close(&rest, &point(48., -15.));
+    }
+
+    #[test]
+    fn snap_glide_keeps_the_speed_at_release() {
+        let time = fixed_time();
+        let mut movement = Movement::default();
+        movement.set_snap_mode(SnapMode::Continuation);
+        let mut translation = point(16., -15.);
+
+        // Still speeding up when we let go
+        movement.set_direction(point(1., 0.));
+        for _ in 0..6 {
+            translation += movement.next(&translation, &time);
+        }
+        let released_at = movement.velocity().norm();
+        assert!(released_at < movement.max_speed);
+
+        movement.set_direction(Vector3::zeros());
+        translation += movement.next(&translation, &time);
+        assert!((movement.velocity().norm().as_f32() - released_at.as_f32()).abs() < 0.001);
+        translation += movement.next(&translation, &time);
+        assert!((movement.velocity().norm().as_f32() - released_at.as_f32()).abs() < 0.001);
+    }
+
+    #[test]
+    fn snapping_lines_up_with_the_collision_grid() {
+        // Walls all around cell (1, 0)
+        let mut grid = CollisionGrid::new(4, 4, (32., 32.), MAP_ORIGIN);
+        grid.set_solid(0, 1, true);
+        grid.set_solid(1, 1, true);
+        grid.set_solid(2, 1, true);
+        grid.set_solid(2, 0, true);
+        let rest = move_right_and_release(SnapMode::Continuation, 6);
+        let (x, y) = (rest.x.as_f32(), rest.y.as_f32());
+        assert_eq!(grid.cell_at(x, y), (1, 0));
+        // Resting in the middle of the cell, so a box just under a tile wide touches no walls
+        assert!(!grid.is_area_blocked((x - 15., y - 15.), (x + 15., y + 15.)));
+    }
+}
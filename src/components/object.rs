@@ -0,0 +1,90 @@
+//! Components for entities placed as objects in a Tiled object layer
+use crate::util::data::WarpTarget;
+
+use amethyst::ecs::{Component, DenseVecStorage};
+use tiled::Properties;
+
+/// A non-player character
+pub struct Npc {
+    /// The object's name in Tiled, so dialogue and scripts can find it again
+    pub name: String,
+}
+
+impl Component for Npc {
+    type Storage = DenseVecStorage<Self>;
+}
+
+/// An area that something happens in when entered. The area is centered on the entity's
+/// transform.
+pub struct Trigger {
+    pub name: String,
+    /// Pixel width and height of the area
+    pub size: (f32, f32),
+    /// Custom properties set on the object in Tiled
+    pub properties: Properties,
+}
+
+impl Trigger {
+    /// Whether a world position lies inside the area
+    ///
+    /// * `center` - world position of the trigger, which is its entity's translation
+    pub fn contains(&self, center: (f32, f32), point: (f32, f32)) -> bool {
+        (point.0 - center.0).abs() <= self.size.0 / 2.0
+            && (point.1 - center.1).abs() <= self.size.1 / 2.0
+    }
+}
+
+impl Component for Trigger {
+    type Storage = DenseVecStorage<Self>;
+}
+
+/// An area that moves the player to another map when stepped on, like a door or a cave entrance.
+/// The area is centered on the entity's transform.
+pub struct Warp {
+    pub target: WarpTarget,
+    /// Pixel width and height of the area
+    pub size: (f32, f32),
+}
+
+impl Warp {
+    /// Whether a world position lies inside the area, edges included. Point warps have no size,
+    /// so they're only entered by standing exactly on them.
+    ///
+    /// * `center` - world position of the warp, which is its entity's translation
+    pub fn contains(&self, center: (f32, f32), point: (f32, f32)) -> bool {
+        (point.0 - center.0).abs() <= self.size.0 / 2.0
+            && (point.1 - center.1).abs() <= self.size.1 / 2.0
+    }
+}
+
+impl Component for Warp {
+    type Storage = DenseVecStorage<Self>;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn warp(size: (f32, f32)) -> Warp {
+        Warp {
+            target: WarpTarget {
+                map: "next.tmx".to_string(),
+                spawn: String::new(),
+            },
+            size,
+        }
+    }
+
+    #[test]
+    fn point_warps_contain_their_own_position() {
+        assert!(warp((0.0, 0.0)).contains((16.0, -16.0), (16.0, -16.0)));
+        assert!(!warp((0.0, 0.0)).contains((16.0, -16.0), (16.5, -16.0)));
+    }
+
+    #[test]
+    fn warp_edges_are_inside() {
+        let warp = warp((32.0, 32.0));
+        assert!(warp.contains((16.0, -16.0), (32.0, 0.0)));
+        assert!(!warp.contains((16.0, -16.0), (32.5, -16.0)));
+    }
+}
@@ -0,0 +1,129 @@
+//! Components for tiles built from the map
+use crate::util::chunk::Rect;
+
+use amethyst::ecs::{Component, DenseVecStorage, Entity};
+
+use std::time::Duration;
+
+/// A tile that cycles through sprites of its tileset, built from a Tiled `<animation>`
+#[derive(Clone, Debug)]
+pub struct AnimatedTile {
+    /// Sprite number in the tileset's sprite sheet, and how long that frame is shown for
+    pub frames: Vec<(usize, Duration)>,
+}
+
+impl Component for AnimatedTile {
+    type Storage = DenseVecStorage<Self>;
+}
+
+/// The tiles of one layer in one chunk. The chunk is shown or hidden as a whole, depending on
+/// whether it's on screen.
+pub struct TileChunk {
+    /// Entities of every tile in this chunk, which are drawn from their tileset's sprite sheet
+    pub tiles: Vec<Entity>,
+    /// The world area covered by this chunk
+    pub bounds: Rect,
+    /// Whether the chunk is currently shown. Entities are only touched when this changes.
+    pub visible: bool,
+}
+
+impl TileChunk {
+    /// Show the chunk if it overlaps `view`, the area the camera sees. Returns whether that
+    /// changed anything.
+    pub fn update_visibility(&mut self, view: &Rect) -> bool {
+        let visible = self.bounds.overlaps(view);
+        let changed = visible != self.visible;
+        self.visible = visible;
+        changed
+    }
+}
+
+impl Component for TileChunk {
+    type Storage = DenseVecStorage<Self>;
+}
+
+/// A tile layer that scrolls at a different speed from the rest of the map. Its tiles are
+/// children of the entity this is on, which is moved along with the camera.
+pub struct ParallaxLayer {
+    /// How fast the layer scrolls compared to the map, where 1 is the same speed and 0 stays
+    /// still on screen
+    pub factor: (f32, f32),
+}
+
+impl Component for ParallaxLayer {
+    type Storage = DenseVecStorage<Self>;
+}
+
+/// Marks everything built from the current map, as opposed to the player and camera, so the map
+/// can be torn down and built again in place
+#[derive(Default)]
+pub struct MapEntity {}
+
+impl Component for MapEntity {
+    type Storage = DenseVecStorage<Self>;
+}
+
+impl AnimatedTile {
+    /// Total time it takes to play every frame once
+    pub fn period(&self) -> Duration {
+        self.frames
+            .iter()
+            .fold(Duration::default(), |total, (_, duration)| total + *duration)
+    }
+
+    /// The sprite to show after the animation has been playing for `elapsed`. Animations loop
+    /// forever, and every tile with the same animation stays in sync since they all start at zero.
+    pub fn sprite_at(&self, elapsed: Duration) -> Option<usize> {
+        let period = self.period().as_millis();
+        if period == 0 {
+            return self.frames.first().map(|(sprite, _)| *sprite);
+        }
+        let mut remaining = elapsed.as_millis() % period;
+        for (sprite, duration) in self.frames.iter() {
+            if remaining < duration.as_millis() {
+                return Some(*sprite);
+            }
+            remaining -= duration.as_millis();
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chunk() -> TileChunk {
+        TileChunk {
+            tiles: Vec::new(),
+            bounds: Rect {
+                left: 0.0,
+                right: 512.0,
+                bottom: -511.0,
+                top: 1.0,
+            },
+            visible: true,
+        }
+    }
+
+    #[test]
+    fn chunks_are_hidden_once_the_view_leaves_them() {
+        let mut chunk = chunk();
+        let view = Rect {
+            left: 100.0,
+            right: 420.0,
+            bottom: -200.0,
+            top: 40.0,
+        };
+        assert!(!chunk.update_visibility(&view));
+        assert!(chunk.visible);
+
+        assert!(chunk.update_visibility(&view.translate(600.0, 0.0)));
+        assert!(!chunk.visible);
+        // Nothing changes while the view stays away
+        assert!(!chunk.update_visibility(&view.translate(700.0, 0.0)));
+
+        assert!(chunk.update_visibility(&view.translate(0.0, -400.0)));
+        assert!(chunk.visible);
+    }
+}
@@ -0,0 +1,98 @@
+//! Runs the game logic without a window or renderer, one fixed step at a time. This is what lets
+//! us exercise movement, map loading and player logic somewhere without a GPU or display, like CI.
+//!
+//! Only the renderer is missing, which means textures never finish loading. The `Headless`
+//! resource tells the load states not to wait on them, so maps load through the same states the
+//! game uses.
+use amethyst::{
+    assets::{AssetStorage, Loader},
+    core::{rayon::ThreadPoolBuilder, ArcThreadPool, Time},
+    ecs::World,
+    prelude::*,
+    renderer::Texture,
+    StateEvent, StateMachine,
+};
+
+use std::{path::Path, sync::Arc, time::Duration};
+
+/// Present when nothing is drawn. Textures are still loaded, so their handles can be handed
+/// around as usual, but they're never processed without a renderer.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Headless;
+
+pub struct Simulation<'a, 'b> {
+    pub world: World,
+    game_data: GameData<'a, 'b>,
+    /// The game's states, once one is started. Without any, stepping only runs the systems.
+    states: Option<StateMachine<'a, GameData<'a, 'b>, StateEvent>>,
+}
+
+impl<'a, 'b> Simulation<'a, 'b> {
+    /// Set up a world with the same logic systems as the game
+    ///
+    /// * `assets_dir` - Root of the assets, the same directory the game loads from
+    pub fn new(assets_dir: &Path) -> amethyst::Result<Self> {
+        let mut world = World::new();
+
+        // The application usually provides these for us
+        let pool: ArcThreadPool = Arc::new(ThreadPoolBuilder::new().build()?);
+        world.add_resource(Loader::new(assets_dir, pool.clone()));
+        world.add_resource(pool);
+        world.add_resource(Time::default());
+        // Usually added by the renderer, which we don't have
+        world.add_resource(AssetStorage::<Texture>::new());
+        world.add_resource(Headless);
+
+        let game_data =
+            crate::with_logic(GameDataBuilder::default(), &assets_dir.join("config"))?.build(&mut world);
+
+        Ok(Self {
+            world,
+            game_data,
+            states: None,
+        })
+    }
+
+    /// Start running `state`, the same way the game starts with its first state
+    pub fn start<S>(&mut self, state: S) -> amethyst::Result<()>
+    where
+        S: State<GameData<'a, 'b>, StateEvent> + 'a,
+    {
+        let mut states = StateMachine::new(state);
+        states.start(StateData::new(&mut self.world, &mut self.game_data))?;
+        self.states = Some(states);
+        Ok(())
+    }
+
+    /// Run every system `frames` times, as if each frame took exactly `delta`. If a state is
+    /// running, it's updated first and runs the systems itself.
+    pub fn step(&mut self, frames: usize, delta: Duration) {
+        for _ in 0..frames {
+            {
+                let mut time = self.world.write_resource::<Time>();
+                time.set_delta_time(delta);
+                time.increment_frame_number();
+            }
+            match self.states.as_mut().filter(|states| states.is_running()) {
+                Some(states) => states.update(StateData::new(&mut self.world, &mut self.game_data)),
+                None => self.game_data.update(&self.world),
+            }
+            self.world.maintain();
+        }
+    }
+
+    /// Step until `done` returns true, giving up after `max_frames`. Returns whether `done` was
+    /// reached, which is handy for waiting on assets.
+    pub fn step_until<F>(&mut self, max_frames: usize, delta: Duration, mut done: F) -> bool
+    where
+        F: FnMut(&World) -> bool,
+    {
+        for _ in 0..max_frames {
+            if done(&self.world) {
+                return true;
+            }
+            self.step(1, delta);
+        }
+        done(&self.world)
+    }
+}
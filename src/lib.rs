@@ -0,0 +1,123 @@
+#![feature(duration_float)]
+
+pub mod components;
+pub mod headless;
+pub mod states;
+pub mod systems;
+pub mod util;
+
+use amethyst::{
+    assets::Processor,
+    core::transform::TransformBundle,
+    input::InputBundle,
+    prelude::*,
+    renderer::sprite::SpriteSheet,
+};
+
+use crate::util::{
+    bindings::load_bindings,
+    data::{CameraConfig, CharacterAnimationConfig, GameBindings, PlayerConfig},
+    manifest::{AssetLoaders, AssetManifest},
+};
+use std::path::Path;
+use tiled::Map;
+
+/// Register everything the game needs that doesn't draw to the screen: asset processors, input,
+/// transforms and all of our game logic systems. The windowed game adds its window and renderer
+/// around this, while headless runs use it on its own.
+///
+/// * `config_dir` - Directory containing `bindings.ron`
+pub fn with_logic<'a, 'b>(
+    builder: GameDataBuilder<'a, 'b>,
+    config_dir: &Path,
+) -> amethyst::Result<GameDataBuilder<'a, 'b>> {
+    Ok(builder
+        .with(
+            Processor::<SpriteSheet>::new(),
+            "sprite_sheet_processor",
+            &[],
+        )
+        .with(Processor::<Map>::new(), "map_processor", &[])
+        .with(Processor::<CameraConfig>::new(), "camera_config_processor", &[])
+        .with(Processor::<PlayerConfig>::new(), "player_config_processor", &[])
+        .with(
+            Processor::<CharacterAnimationConfig>::new(),
+            "character_animation_config_processor",
+            &[],
+        )
+        .with(Processor::<AssetManifest>::new(), "asset_manifest_processor", &[])
+        .with_bundle(
+            InputBundle::<GameBindings>::new()
+                .with_bindings(load_bindings(&config_dir.join("bindings.ron"))),
+        )?
+        .with_bundle(TransformBundle::new())?
+        .with(
+            systems::GridMovementSystem::default(),
+            "grid_movement_system",
+            &["transform_system"],
+        )
+        .with(
+            systems::MovementSystem::default(),
+            "movement_system",
+            &["transform_system"],
+        )
+        .with(
+            systems::PlayerSystem::default(),
+            "player_system",
+            &["transform_system", "movement_system"],
+        )
+        .with(
+            systems::CharacterAnimationSystem::default(),
+            "character_animation_system",
+            &["player_system"],
+        )
+        .with(
+            systems::TileAnimationSystem::default(),
+            "tile_animation_system",
+            &[],
+        )
+        .with(
+            systems::CameraScalingSystem::default(),
+            "camera_scaling_system",
+            &[],
+        )
+        .with(
+            systems::CameraFollowSystem::default(),
+            "camera_follow_system",
+            &["movement_system", "grid_movement_system", "camera_scaling_system"],
+        )
+        .with(
+            systems::DepthSortSystem::default(),
+            "depth_sort_system",
+            &["movement_system", "grid_movement_system"],
+        )
+        .with(
+            systems::ParallaxSystem::default(),
+            "parallax_system",
+            &["camera_follow_system"],
+        )
+        .with(
+            systems::ChunkCullingSystem::default(),
+            "chunk_culling_system",
+            &["transform_system", "camera_follow_system"],
+        )
+        .with(
+            systems::RebindSystem::new(config_dir.join("bindings.ron")),
+            "rebind_system",
+            &["input_system"],
+        )
+        .with(
+            systems::WarpSystem::default(),
+            "warp_system",
+            &["movement_system", "grid_movement_system"],
+        ))
+}
+
+/// Loaders for every kind of asset the game lists in its manifest. Load states put these in the
+/// world when nothing else has, so anything running the game can register more kinds first.
+pub fn asset_loaders() -> AssetLoaders {
+    AssetLoaders::default()
+        .with_ron::<CameraConfig>("CameraConfig")
+        .with_ron::<PlayerConfig>("PlayerConfig")
+        .with_ron::<CharacterAnimationConfig>("CharacterAnimation")
+}
@@ -1,18 +1,8 @@
-#![feature(duration_float)]
-
-mod components;
-mod states;
-mod systems;
-mod util;
-
 use amethyst::{
-    assets::{ProgressCounter, Processor},
-    core::transform::TransformBundle,
     ecs::{ReadExpect, Resources, SystemData},
-    input::InputBundle,
     prelude::*,
     renderer::{
-        pass::DrawFlat2DDesc,
+        pass::{DrawFlat2DDesc, DrawFlat2DTransparentDesc},
         rendy::{
             factory::Factory,
             graph::{
@@ -21,7 +11,6 @@ use amethyst::{
             },
             hal::{format::Format, image},
         },
-        sprite::SpriteSheet,
         types::DefaultBackend,
         GraphCreator, RenderingSystem,
     },
@@ -29,12 +18,8 @@ use amethyst::{
     window::{ScreenDimensions, Window, WindowBundle},
 };
 
-use crate::{
-    states::LoadMapState,
-    util::data::CameraConfig,
-};
+use j::{states::LoadMapState, systems::HotReloadSystem};
 use std::sync::Arc;
-use tiled::Map;
 
 fn main() -> amethyst::Result<()> {
     amethyst::start_logger(Default::default());
@@ -43,49 +28,30 @@ fn main() -> amethyst::Result<()> {
     let assets_dir = root_dir.join("resources");
     let config_dir = assets_dir.join("config");
 
-    let game_data = GameDataBuilder::default()
-        // The WindowBundle provides all the scaffolding for opening a window and drawing to it
-        .with_bundle(WindowBundle::from_config_path(
-            config_dir.join("display.ron"),
-        ))?
-        .with(
-            Processor::<SpriteSheet>::new(),
-            "sprite_sheet_processor",
-            &[],
-        )
-        .with(Processor::<Map>::new(), "map_processor", &[])
-        .with(Processor::<CameraConfig>::new(), "camera_config_processor", &[])
-        .with_bundle(
-            InputBundle::<util::data::GameBindings>::new()
-                .with_bindings_from_file(config_dir.join("bindings.ron"))?,
-        )?
-        .with_bundle(TransformBundle::new())?
-        .with(
-            systems::GridMovementSystem::default(),
-            "grid_movement_system",
-            &["transform_system"],
-        )
-        .with(
-            systems::MovementSystem::default(),
-            "movement_system",
-            &["transform_system"],
-        )
-        .with(
-            systems::PlayerSystem::default(),
-            "player_system",
-            &["transform_system", "movement_system"],
-        )
+    // The WindowBundle provides all the scaffolding for opening a window and drawing to it
+    let game_data = GameDataBuilder::default().with_bundle(WindowBundle::from_config_path(
+        config_dir.join("display.ron"),
+    ))?;
+    // Then all of the game logic, which is shared with headless runs
+    let game_data = j::with_logic(game_data, &config_dir)?;
+    // While developing, reload maps and configs as soon as they're saved
+    let game_data = if cfg!(debug_assertions) {
+        game_data.with(HotReloadSystem::new(assets_dir.clone()), "hot_reload_system", &[])
+    } else {
+        game_data
+    };
+    let game_data = game_data
         // The renderer must be executed on the same thread consecutively, so we initialize it as thread_local
         // which will always execute on the main thread.
         .with_thread_local(RenderingSystem::<DefaultBackend, _>::new(
             ExampleGraph::default(),
         ));
 
-    let mut game = Application::new(assets_dir, LoadMapState {
-        progress_counter: ProgressCounter::new(),
-        map_handle: None,
-        path: "maps/first.tmx",
-    }, game_data)?;
+    let mut game = Application::new(
+        assets_dir,
+        LoadMapState::new("maps/first.tmx", None),
+        game_data,
+    )?;
     game.run();
     Ok(())
 }
@@ -166,10 +132,12 @@ impl GraphCreator<DefaultBackend> for ExampleGraph {
         );
 
         // Create our single `Subpass`, which is the DrawFlat2D pass.
-        // We pass the subpass builder a description of our pass for construction
+        // We pass the subpass builder a description of our pass for construction.
+        // Sprites marked `Transparent`, like tiles on see-through layers, are blended on top.
         let sprite = graph_builder.add_node(
             SubpassBuilder::new()
                 .with_group(DrawFlat2DDesc::new().builder())
+                .with_group(DrawFlat2DTransparentDesc::new().builder())
                 .with_color(color)
                 .with_depth_stencil(depth)
                 .into_pass(),
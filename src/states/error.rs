@@ -0,0 +1,44 @@
+use crate::util::loading::{LoadError, LoadFailure};
+
+use amethyst::{
+    input::{is_close_requested, is_key_down, VirtualKeyCode},
+    prelude::*,
+};
+use log::error;
+
+/// Where we end up when the assets we need can't be loaded. There's nothing to show without them,
+/// so this logs what went wrong and waits for the window to be closed.
+pub struct ErrorState {
+    pub errors: Vec<LoadError>,
+}
+
+impl ErrorState {
+    pub fn new(errors: Vec<LoadError>) -> Self {
+        Self { errors }
+    }
+}
+
+impl SimpleState for ErrorState {
+    fn on_start(&mut self, data: StateData<'_, GameData<'_, '_>>) {
+        for load_error in self.errors.iter() {
+            error!("Could not load {}", load_error);
+        }
+        error!("The game can't continue. Press Escape or close the window to quit.");
+        data.world.add_resource(LoadFailure {
+            errors: self.errors.clone(),
+        });
+    }
+
+    fn handle_event(
+        &mut self,
+        _data: StateData<'_, GameData<'_, '_>>,
+        event: StateEvent,
+    ) -> SimpleTrans {
+        if let StateEvent::Window(event) = &event {
+            if is_close_requested(event) || is_key_down(event, VirtualKeyCode::Escape) {
+                return Trans::Quit;
+            }
+        }
+        Trans::None
+    }
+}
@@ -1,164 +1,288 @@
 use crate::{
-    states::MainGameState,
-    util::data::CameraConfig};
+    headless::Headless,
+    states::{ErrorState, MainGameState},
+    util::{
+        loading::{load_status, LoadDeadline, LoadError, LoadProgress, LoadStatus},
+        manifest::{AssetHandles, AssetLoaders, AssetManifest},
+        path::resolve_relative,
+        tmx::{MapFormat, TilesetCache},
+    }};
 
 use amethyst::{
     assets::{AssetStorage, Handle, Loader, ProgressCounter, RonFormat},
+    core::Time,
     prelude::*,
     renderer::{
         formats::texture::ImageFormat,
         Texture,
-        sprite::{SpriteSheet, SpriteSheetFormat},
     },
 };
 
 
-use tiled::{Map, TmxFormat};
+use log::warn;
+use tiled::Map;
+
+/// Path of the asset manifest, listing everything besides the map to load before a map starts
+pub const MANIFEST_PATH: &str = "config/assets.ron";
 
 /// Because our map asset contains the paths to textures, we need two load states: one
-/// for loading the map file (this one), and another for loading the textures.
-pub struct LoadMapState<'a> {
+/// for loading the map file and the asset manifest (this one), and another for loading the
+/// textures and everything in the manifest. The manifest is only read for the first map, later
+/// maps reuse the `AssetHandles` it was loaded into.
+pub struct LoadMapState {
     /// Tracks loaded assets.
     pub progress_counter: ProgressCounter,
     /// Handle to the map
     pub map_handle: Option<Handle<Map>>,
-    pub path: &'a str,
+    pub manifest_handle: Option<Handle<AssetManifest>>,
+    pub path: String,
+    /// Name of the spawn point to place the player at. If `None`, the map's first one is used.
+    pub spawn: Option<String>,
+    pub deadline: LoadDeadline,
+}
+
+impl LoadMapState {
+    pub fn new(path: &str, spawn: Option<String>) -> Self {
+        Self {
+            progress_counter: ProgressCounter::new(),
+            map_handle: None,
+            manifest_handle: None,
+            path: path.to_string(),
+            spawn,
+            deadline: LoadDeadline::default(),
+        }
+    }
 }
 
-impl<'a> SimpleState for LoadMapState<'a> {
+impl SimpleState for LoadMapState {
     fn on_start(&mut self, data: StateData<'_, GameData<'_, '_>>) {
+        // External tilesets are shared between maps, so they're only read once
+        let tileset_cache = data
+            .world
+            .res
+            .entry::<TilesetCache>()
+            .or_insert_with(TilesetCache::default)
+            .clone();
         let loader = &data.world.read_resource::<Loader>();
         let map_handle = loader.load(
-            self.path,
-            TmxFormat,
+            self.path.as_str(),
+            MapFormat::new(tileset_cache),
             &mut self.progress_counter,
             &data.world.read_resource::<AssetStorage<Map>>(),
         );
 
         self.map_handle = Some(map_handle);
+
+        if !data.world.res.has_value::<AssetHandles>() {
+            self.manifest_handle = Some(loader.load(
+                MANIFEST_PATH,
+                RonFormat,
+                &mut self.progress_counter,
+                &data.world.read_resource::<AssetStorage<AssetManifest>>(),
+            ));
+        }
     }
 
     fn update(
         &mut self,
-        _data: &mut StateData<'_, GameData<'_, '_>>,
+        data: &mut StateData<'_, GameData<'_, '_>>,
     ) -> SimpleTrans {
-        if self.progress_counter.is_complete() {
-            Trans::Switch(Box::new(LoadDataState {
-                map_handle: Some(self.map_handle
-                    .take()
-                    .expect(
-                        "Expected `map_handle` to exist when \
-                        `progress_counter` is complete."
-                    )),
+        match load_status(&[&self.progress_counter]) {
+            LoadStatus::Loaded => Trans::Switch(Box::new(LoadDataState {
+                map_path: self.path.clone(),
+                spawn: self.spawn.take(),
+                map_handle: self.map_handle.take(),
+                manifest_handle: self.manifest_handle.take(),
                 ..LoadDataState::default()
-            }))
-        } else {
-            Trans::None
+            })),
+            LoadStatus::Failed(errors) => Trans::Switch(Box::new(ErrorState::new(errors))),
+            LoadStatus::Loading => {
+                let now = data.world.read_resource::<Time>().absolute_real_time();
+                if self.deadline.expired(now) {
+                    Trans::Switch(Box::new(ErrorState::new(vec![LoadError::timed_out(&self.path, self.deadline.timeout())])))
+                } else {
+                    Trans::None
+                }
+            }
         }
     }
 }
 
+/// Loads the tileset textures of the map, and every asset in the manifest
 pub struct LoadDataState {
-    /// Tracks loaded assets.
-    pub map_texture_progress: ProgressCounter,
-    pub camera_config_progress: ProgressCounter,
-    pub player_spritesheet_progress: ProgressCounter,
-    /// Handle to the map texture. In the future, we may have multiple.
-    pub texture_handle: Option<Handle<Texture>>,
-    pub camera_config_handle: Option<Handle<CameraConfig>>,
+    /// Asset path of the map, so paths inside of it can be resolved relative to it
+    pub map_path: String,
+    pub spawn: Option<String>,
+    /// Tracks every asset we load besides textures, so we can tell how far along we are
+    pub progress: ProgressCounter,
+    /// Tracks the textures we load. Without a renderer they never finish, so headless runs
+    /// don't wait on these.
+    pub textures: ProgressCounter,
     pub map_handle: Option<Handle<Map>>,
-    pub player_spritesheet_handle: Option<Handle<SpriteSheet>>,
+    pub manifest_handle: Option<Handle<AssetManifest>>,
+    /// Handles to the map's images, one for every tileset in the map. Tilesets without a
+    /// single image to load have none.
+    pub tileset_handles: Vec<Option<Handle<Texture>>>,
+    /// Everything loaded from the manifest, when this is the first map and we're loading it.
+    /// Once everything is loaded it becomes a resource.
+    pub assets: Option<AssetHandles>,
+    /// Problems found before anything was loaded, like a tileset image we can't find a path to
+    pub errors: Vec<LoadError>,
+    pub deadline: LoadDeadline,
 }
 
 impl Default for LoadDataState {
     fn default() -> Self {
         Self {
-            map_texture_progress: ProgressCounter::new(),
-            camera_config_progress: ProgressCounter::new(),
-            player_spritesheet_progress: ProgressCounter::new(),
-            texture_handle: None,
-            camera_config_handle: None,
+            map_path: String::new(),
+            spawn: None,
+            progress: ProgressCounter::new(),
+            textures: ProgressCounter::new(),
             map_handle: None,
-            player_spritesheet_handle: None,
+            manifest_handle: None,
+            tileset_handles: Vec::new(),
+            assets: None,
+            errors: Vec::new(),
+            deadline: LoadDeadline::default(),
         }
     }
 }
 
 impl<'a> SimpleState for LoadDataState {
     fn on_start(&mut self, data: StateData<'_, GameData<'_, '_>>) {
+        // Everything in the manifest, like our configs and the player's sprites, unless an
+        // earlier map already loaded it. The manifest is already loaded in by the previous load
+        // state.
+        if !data.world.res.has_value::<AssetHandles>() {
+            let manifest = {
+                let manifest_storage = data.world.read_resource::<AssetStorage<AssetManifest>>();
+                self.manifest_handle
+                    .as_ref()
+                    .and_then(|handle| manifest_storage.get(handle))
+                    .cloned()
+            };
+            let manifest = match manifest {
+                Some(manifest) => manifest,
+                None => {
+                    self.errors.push(LoadError::new(MANIFEST_PATH, "the manifest is not loaded".to_string()));
+                    return;
+                }
+            };
+            data.world
+                .res
+                .entry::<AssetLoaders>()
+                .or_insert_with(crate::asset_loaders);
+            self.assets = Some(manifest.load(
+                data.world,
+                &data.world.read_resource::<AssetLoaders>(),
+                &mut self.progress,
+                &mut self.textures,
+                &mut self.errors,
+            ));
+        }
+
         let loader = &data.world.read_resource::<Loader>();
 
-        // Get our map, which is already definitely loaded in by the previous load state.
+        // Get our map, which should already be loaded in by the previous load state.
         let map_storage = &data.world.read_resource::<AssetStorage<Map>>();
-        let map = &map_storage.get(&self.map_handle.clone().unwrap()).unwrap();
-
-        // Now, get our map texture which is defined in the tilemap file.
-        let texture_handle = loader.load(
-            // This is defined as a relative path in our TMX. Let's get rid of it.
-            // Quick and dirty. Much of this should be refactored.
-            &map.tilesets[0].images[0].source.clone()[3..],
-            ImageFormat::default(),
-            &mut self.map_texture_progress,
-            &data.world.read_resource::<AssetStorage<Texture>>(),
-        );
-        self.texture_handle = Some(texture_handle);
+        let map = match self.map_handle.as_ref().and_then(|handle| map_storage.get(handle)) {
+            Some(map) => map,
+            None => {
+                self.errors.push(LoadError::new(&self.map_path, "the map is not loaded".to_string()));
+                return;
+            }
+        };
 
-        // Here, we will also get data for a bunch of other on-start stuff, like our camera
-        self.camera_config_handle = Some(
-            loader.load(
-                "config/camera.ron",
-                RonFormat,
-                &mut self.camera_config_progress,
-                &data.world.read_resource::<AssetStorage<CameraConfig>>(),
-            )
-        );
-
-        // Load our player spritesheet
-        self.player_spritesheet_handle = Some({
+        // Now, get our map textures which are defined in the tilemap file. Every tileset has its
+        // own image, which all of its tiles are drawn from.
+        let texture_storage = &data.world.read_resource::<AssetStorage<Texture>>();
+        for tileset in map.tilesets.iter() {
+            // Every tileset gets an entry, even without handles, so they stay in the same order
+            // as the map's tilesets
+            let image = match tileset.images.first() {
+                Some(image) => image,
+                None => {
+                    // Tilesets made of one image per tile have nothing we can slice
+                    warn!("The tileset `{}` has no tileset image, so its tiles won't be drawn", tileset.name);
+                    self.tileset_handles.push(None);
+                    continue;
+                }
+            };
+            // This is defined as a path relative to our TMX, so we resolve it against the map's
+            // location to get an asset path.
+            let texture_path = match resolve_relative(&self.map_path, &image.source) {
+                Ok(texture_path) => texture_path,
+                Err(error) => {
+                    self.errors.push(LoadError::new(&image.source, error.to_string()));
+                    self.tileset_handles.push(None);
+                    continue;
+                }
+            };
             let texture = loader.load(
-                "textures/chars.png",
+                texture_path.as_str(),
                 ImageFormat::default(),
-                (),
-                &data.world.read_resource::<AssetStorage<Texture>>(),
+                &mut self.textures,
+                texture_storage,
             );
-            // create the actual spritesheet
-            loader.load(
-                "textures/chars.png.ron",
-                SpriteSheetFormat(texture),
-                &mut self.player_spritesheet_progress,
-                &data.world.read_resource::<AssetStorage<SpriteSheet>>(),
-            )}
-        )
+            self.tileset_handles.push(Some(texture));
+        }
     }
 
     fn update(
         &mut self,
-        _data: &mut StateData<'_, GameData<'_, '_>>,
+        data: &mut StateData<'_, GameData<'_, '_>>,
     ) -> SimpleTrans {
-        // check all our progress counters, and only move on once all our states are loaded.
-        if self.is_complete() {
-            Trans::Switch(Box::new(self.build_main_game_state()))
-        } else {
-            Trans::None
+        if !self.errors.is_empty() {
+            let errors = std::mem::replace(&mut self.errors, Vec::new());
+            return Trans::Switch(Box::new(ErrorState::new(errors)));
+        }
+
+        let mut counters = vec![&self.progress];
+        if !data.world.res.has_value::<Headless>() {
+            counters.push(&self.textures);
+        }
+
+        // Anything that wants to show how far along we are can read this
+        data.world.add_resource(LoadProgress {
+            loaded: counters.iter().map(|counter| counter.num_finished()).sum(),
+            total: counters.iter().map(|counter| counter.num_assets()).sum(),
+        });
+
+        match load_status(&counters) {
+            LoadStatus::Loaded => match self.build_main_game_state(data.world) {
+                Ok(state) => Trans::Switch(Box::new(state)),
+                Err(errors) => Trans::Switch(Box::new(ErrorState::new(errors))),
+            },
+            LoadStatus::Failed(errors) => Trans::Switch(Box::new(ErrorState::new(errors))),
+            LoadStatus::Loading => {
+                let now = data.world.read_resource::<Time>().absolute_real_time();
+                if self.deadline.expired(now) {
+                    let asset = format!("assets for `{}`", self.map_path);
+                    let error = LoadError::timed_out(&asset, self.deadline.timeout());
+                    Trans::Switch(Box::new(ErrorState::new(vec![error])))
+                } else {
+                    Trans::None
+                }
+            }
         }
     }
 }
 
 impl LoadDataState {
-    /// Because we can have N number of progresses to keep track of, we can separate it out
-    /// into its own implementation so we don't mess up our update function.
-    fn is_complete(&self) -> bool {
-        self.map_texture_progress.is_complete() && 
-        self.camera_config_progress.is_complete() &&
-        self.player_spritesheet_progress.is_complete()
-    }
-
-    fn build_main_game_state(&mut self) -> MainGameState {
-        MainGameState {
-            map_handle: self.map_handle.take().unwrap(),
-            texture_handle: self.texture_handle.take().unwrap(),
-            camera_config_handle: self.camera_config_handle.take().unwrap(),
-            player_spritesheet_handle: self.player_spritesheet_handle.take().unwrap(),
+    fn build_main_game_state(&mut self, world: &mut World) -> Result<MainGameState, Vec<LoadError>> {
+        let map_handle = self
+            .map_handle
+            .take()
+            .ok_or_else(|| vec![LoadError::new(&self.map_path, "the map was never loaded".to_string())])?;
+        if let Some(assets) = self.assets.take() {
+            world.add_resource(assets);
         }
+        MainGameState::new(
+            world,
+            self.map_path.clone(),
+            self.spawn.take(),
+            &map_handle,
+            std::mem::replace(&mut self.tileset_handles, Vec::new()),
+        )
     }
-}
\ No newline at end of file
+}
@@ -1,140 +1,409 @@
 //! Boilerplate for common actions like setting up a camera or loading a sprite sheet
 use crate::{
     components::*, 
+    states::{load::MANIFEST_PATH, ErrorState, LoadMapState},
     util,
-    util::data::CameraConfig,
+    util::{
+        collision::{self, CollisionGrid},
+        data::{
+            CameraConfig, CameraView, CharacterAnimationConfig, MapBounds, MovementKind, PendingWarp, PlayerConfig,
+            WarpTarget, MAP_ORIGIN,
+        },
+        chunk::{ChunkLayout, CHUNK_SIZE},
+        flip::decode_gid,
+        layer::LayerSettings,
+        loading::{load_status, LoadError, LoadStatus},
+        manifest::AssetHandles,
+        objects::{self, MapObject, SpawnRegistry},
+        path::resolve_relative,
+        tileset::TileData,
+        tmx::{MapFormat, TilesetCache},
+        watch::HotReload,
+    },
 };
 
 use amethyst::{
-    assets::{AssetStorage, Handle, Loader},
-    core::math::{Vector2, Vector3},
-    core::transform::{Transform, Parent},
+    assets::{Asset, AssetStorage, Handle, Loader, ProgressCounter},
+    core::math::Vector3,
+    core::transform::{Parent, Transform},
     core::Float,
-    ecs::{prelude::*, Read, Write},
+    ecs::{error::WrongGeneration, prelude::*, Read, Write},
     prelude::*,
     renderer::{
         sprite::{
             Sprite, SpriteRender, SpriteSheet, SpriteSheetHandle,
-            TextureCoordinates
         },
         Texture,
-        camera::{Camera, Projection},
+        camera::Camera,
+        palette::Srgba,
+        resources::Tint,
+        transparent::Transparent,
     },
+    window::ScreenDimensions,
 };
+use log::{info, warn};
 
+use std::{collections::HashMap, time::Duration};
+use tiled::Map;
 
-use tiled::{Map};
+/// Ids in the asset manifest of the assets the game can't start without
+pub const CAMERA_CONFIG: &str = "camera_config";
+pub const PLAYER_CONFIG: &str = "player_config";
+pub const PLAYER_SPRITES: &str = "player_sprites";
+pub const PLAYER_ANIMATION: &str = "player_animation";
 
 pub struct MainGameState {
-    pub map_handle: Handle<Map>,
-    pub texture_handle: Handle<Texture>,
-    pub camera_config_handle: Handle<CameraConfig>,
-    pub player_spritesheet_handle: Handle<SpriteSheet>,
+    /// Asset path of the map, so warps on it can be resolved relative to it
+    pub map_path: String,
+    /// Name of the spawn point to place the player at
+    pub spawn: Option<String>,
+    /// The map being played. Kept here rather than as a handle, since everything in it has been
+    /// checked to be loaded before we start.
+    pub map: Map,
+    /// Images of every tileset, in the same order as `Map::tilesets`. Tilesets without an image
+    /// have none, and their tiles aren't drawn.
+    pub tilesets: Vec<Option<Handle<Texture>>>,
+    /// A newer version of the map being loaded by hot reloading. Tileset images aren't reloaded,
+    /// so adding a tileset still needs a restart.
+    pub reloading: Option<(ProgressCounter, Handle<Map>)>,
+    /// Required assets that went missing between making this state and starting it. There's no
+    /// map or player without them, so the first update switches to the `ErrorState`.
+    pub failed: Option<Vec<LoadError>>,
+    /// The player and camera built when this state started. They're torn down along with the
+    /// map, since the next map builds its own.
+    pub player: Option<Entity>,
+    pub camera: Option<Entity>,
 }
 
 impl SimpleState for MainGameState {
     fn on_start(&mut self, data: StateData<'_, GameData<'_, '_>>) {
-        // create the map and other loaded stuff
+        // Checked when the state was made, so these are only missing if something removed them
+        let assets = match RequiredAssets::find(data.world) {
+            Ok(assets) => assets,
+            Err(errors) => {
+                self.failed = Some(errors);
+                return;
+            }
+        };
+
+        // Build the player, and the camera that follows them around
         type SystemData<'a> = (
             Entities<'a>,
-            Write<'a, AssetStorage<Map>>,
-            Write<'a, AssetStorage<CameraConfig>>,
+            Read<'a, AssetStorage<SpriteSheet>>,
             WriteStorage<'a, Player>,
+            WriteStorage<'a, CharacterAnimation>,
             WriteStorage<'a, Movement>,
+            WriteStorage<'a, GridMovement>,
             WriteStorage<'a, Transform>,
             WriteStorage<'a, SpriteRender>,
             WriteStorage<'a, Camera>,
-            WriteStorage<'a, Parent>,
-            Read<'a, AssetStorage<SpriteSheet>>,
-            ReadExpect<'a, Loader>,
+            WriteStorage<'a, CameraFollow>,
+            WriteStorage<'a, DepthSort>,
+            Option<Read<'a, ScreenDimensions>>,
         );
 
-        data.world.exec(
+        let (player, camera) = data.world.exec(
         |(
             entities,
-            map_storage,
-            camera_config_storage,
+            sprite_sheet_storage,
             mut player_storage,
+            mut character_animation_storage,
             mut movement_storage,
+            mut grid_movement_storage,
             mut transform_storage,
             mut sprite_render_storage,
             mut camera_storage,
-            mut parent_storage,
-            sprite_sheet_storage,
-            loader,
+            mut camera_follow_storage,
+            mut depth_sort_storage,
+            screen,
         ): SystemData| {
+            let map = &self.map;
 
-            // Build the player
+            // Build the player, at the map's spawn point if it has one
+            let player_config = &assets.player_config;
+            let mut spawn = objects::player_spawn(map, self.spawn.as_ref().map(String::as_str))
+                .unwrap_or_else(Vector3::zeros);
+            let tile_size = (map.tile_width as f32, map.tile_height as f32);
+            if let MovementKind::Grid = player_config.movement {
+                // Grid movers step from cell to cell, so they have to start in the middle of one
+                let grid = CollisionGrid::new(map.width as usize, map.height as usize, tile_size, MAP_ORIGIN);
+                let (column, row) = grid.cell_at(spawn.x.as_f32(), spawn.y.as_f32());
+                let (x, y) = grid.cell_center(column, row);
+                spawn.x = Float::from(x);
+                spawn.y = Float::from(y);
+            }
+            let player_transform = Transform::from(spawn);
+            let player_translation = player_transform.translation().clone();
+            // The player's feet are at the bottom of their sprite, which is centered on them
+            let foot_offset = sprite_sheet_storage
+                .get(&assets.player_sprites)
+                .and_then(|sheet| sheet.sprites.first())
+                .map(|sprite| sprite.height / 2.0)
+                .unwrap_or(0.0);
             let player = entities
                 .build_entity()
-                .with(Transform::default(), &mut transform_storage)
+                .with(player_transform, &mut transform_storage)
                 .with(SpriteRender {
-                    sprite_sheet: self.player_spritesheet_handle.clone(),
+                    sprite_sheet: assets.player_sprites.clone(),
                     sprite_number: 0,
                 }, &mut sprite_render_storage)
-                .with(Movement::default(), &mut movement_storage)
                 .with(Player::default(), &mut player_storage)
-                .build();
+                .with(DepthSort { foot_offset }, &mut depth_sort_storage)
+                .with(CharacterAnimation::new(assets.player_animation.clone()), &mut character_animation_storage);
+            // The player either moves freely or one tile at a time, depending on the config
+            let player = match player_config.movement {
+                MovementKind::Free => {
+                    let mut movement = Movement::new(
+                        Float::from(player_config.speed.max_speed),
+                        Float::from(player_config.speed.acceleration),
+                        Float::from(player_config.speed.deceleration),
+                    );
+                    movement.run_multiplier = Float::from(player_config.speed.run_multiplier);
+                    movement.set_snap_mode(player_config.snap_mode);
+                    // Our feet are a bit narrower than a tile, so one tile wide doors are easy
+                    // to walk through
+                    movement.size = Float::from(map.tile_width as f32);
+                    movement.feet = (
+                        Float::from(map.tile_width as f32 * 0.375),
+                        Float::from(map.tile_height as f32 * 0.375),
+                    );
+                    player.with(movement, &mut movement_storage)
+                }
+                MovementKind::Grid => {
+                    let mut grid_movement = GridMovement::new(
+                        player_translation,
+                        (Float::from(tile_size.0), Float::from(tile_size.1)),
+                    );
+                    grid_movement.duration = Duration::from_millis(player_config.speed.step_duration);
+                    grid_movement.run_duration =
+                        Duration::from_millis(player_config.speed.run_step_duration);
+                    player.with(grid_movement, &mut grid_movement_storage)
+                }
+            };
+            let player = player.build();
 
-            // Build the camera
-            let camera_config = camera_config_storage.get(&self.camera_config_handle.clone()).unwrap();
-            entities
+            // Build the camera, starting centered on the player but never showing past the map
+            let camera_config = &assets.camera_config;
+            let camera_view = CameraView::new(
+                camera_config,
+                screen.map(|screen| (screen.width(), screen.height())),
+            );
+            let (offset_x, offset_y) = camera_view.center_offset;
+            let mut camera_center = (player_translation.x.as_f32(), player_translation.y.as_f32());
+            if let Some(bounds) = MapBounds::from_map(map).0 {
+                camera_center = clamp_center(camera_center, camera_view.half_size, &bounds);
+            }
+            let camera = entities
                 .build_entity()
-                .with(Camera::from(
-                    Projection::orthographic(
-                        camera_config.origin.0 as f32,
-                        camera_config.size.0 as f32,
-                        camera_config.origin.1 as f32,
-                        camera_config.size.1 as f32,
-                        camera_config.znear,
-                        camera_config.zfar,
-                    )
-                ), &mut camera_storage)
+                .with(camera_view.camera(camera_config), &mut camera_storage)
                 .with(Transform::from(Vector3::new(
-                     Float::from(0.0),
-                     Float::from(0.0),
+                     Float::from(camera_view.snap(camera_center.0 - offset_x)),
+                     Float::from(camera_view.snap(camera_center.1 - offset_y)),
                      Float::from(1.0),
                 )), &mut transform_storage)
-                .with(Parent::new(player), &mut parent_storage)
+                .with(CameraFollow {
+                    target: player,
+                    deadzone: camera_config.deadzone,
+                    smoothing: camera_config.smoothing,
+                    look_ahead: camera_config.look_ahead,
+                }, &mut camera_follow_storage)
                 .build();
+            (player, camera)
+        });
+        self.player = Some(player);
+        self.camera = Some(camera);
+
+        self.build_map(data.world);
+
+        // The player system reads how to resolve its input from the player config
+        data.world.add_resource(assets.player_config.input.clone());
+
+        // Systems that need to know what the camera sees read its config as a resource
+        data.world.add_resource(assets.camera_config);
+
+        // While developing, the hot reload system watches the map file and tells us when it changes
+        data.world.add_resource(HotReload {
+            map: Some(self.map_path.clone()),
+            map_changed: false,
+        });
+    }
+
+    fn on_stop(&mut self, data: StateData<'_, GameData<'_, '_>>) {
+        // Only what this state built is torn down, so resources and entities that belong to
+        // other states survive a warp
+        let mut built = map_entities(data.world);
+        built.extend(self.player.take());
+        built.extend(self.camera.take());
+        if let Err(error) = data.world.delete_entities(&built) {
+            warn!("Could not remove the map `{}`: {}", self.map_path, error);
+        }
+    }
+
+    fn update(
+        &mut self,
+        data: &mut StateData<'_, GameData<'_, '_>>,
+    ) -> SimpleTrans {
+        if let Some(errors) = self.failed.take() {
+            return Trans::Switch(Box::new(ErrorState::new(errors)));
+        }
+
+        // The map changed on disk, so load it again. The player and camera stay where they are.
+        if std::mem::replace(&mut data.world.write_resource::<HotReload>().map_changed, false) {
+            // Its external tilesets may have changed along with it, so read those again too
+            let tileset_cache = data
+                .world
+                .res
+                .entry::<TilesetCache>()
+                .or_insert_with(TilesetCache::default)
+                .clone();
+            if let Err(error) = tileset_cache.clear() {
+                warn!("{}", error);
+            }
+            let mut progress = ProgressCounter::new();
+            let map_handle = data.world.read_resource::<Loader>().load(
+                self.map_path.as_str(),
+                MapFormat::new(tileset_cache),
+                &mut progress,
+                &data.world.read_resource::<AssetStorage<Map>>(),
+            );
+            self.reloading = Some((progress, map_handle));
+        }
+        let reload_status = self
+            .reloading
+            .as_ref()
+            .map(|(progress, _)| load_status(&[progress]));
+        match reload_status {
+            Some(LoadStatus::Loaded) => {
+                let reloaded = self.reloading.take().and_then(|(_, map_handle)| {
+                    data.world.read_resource::<AssetStorage<Map>>().get(&map_handle).cloned()
+                });
+                match reloaded {
+                    Some(map) => match self.rebuild_map(data.world, map) {
+                        Ok(()) => info!("Reloaded `{}`", self.map_path),
+                        Err(error) => warn!(
+                            "Could not remove the old `{}`, keeping it: {}",
+                            self.map_path, error
+                        ),
+                    },
+                    None => warn!("Could not reload `{}`, keeping the old map", self.map_path),
+                }
+            }
+            Some(LoadStatus::Failed(errors)) => {
+                // Most likely saved halfway through an edit, so keep the map we have
+                self.reloading = None;
+                for error in errors.iter() {
+                    warn!("Could not reload {}, keeping the old map", error);
+                }
+            }
+            _ => (),
+        }
+
+        // Stepping on a warp loads its map through the same pipeline we started with
+        match data.world.write_resource::<PendingWarp>().0.take() {
+            Some(warp) => match resolve_relative(&self.map_path, &warp.map) {
+                Ok(path) => Trans::Switch(Box::new(LoadMapState::new(&path, Some(warp.spawn)))),
+                Err(error) => Trans::Switch(Box::new(ErrorState::new(vec![
+                    LoadError::new(&warp.map, error.to_string()),
+                ]))),
+            },
+            None => Trans::None,
+        }
+    }
+}
+
+impl MainGameState {
+    /// Start a map with the assets loaded from the manifest, which are read from the
+    /// `AssetHandles` resource. Fails with every required asset that's missing from them or
+    /// hasn't finished loading, so nothing after this has to check.
+    pub fn new(
+        world: &World,
+        map_path: String,
+        spawn: Option<String>,
+        map_handle: &Handle<Map>,
+        tilesets: Vec<Option<Handle<Texture>>>,
+    ) -> Result<Self, Vec<LoadError>> {
+        let mut errors = Vec::new();
+        let map = loaded(world, Some(map_handle.clone()), &map_path, &mut errors);
+        if let Err(missing) = RequiredAssets::find(world) {
+            errors.extend(missing);
+        }
+        match map {
+            Some(map) if errors.is_empty() => Ok(Self {
+                map_path,
+                spawn,
+                map,
+                tilesets,
+                reloading: None,
+                failed: None,
+                player: None,
+                camera: None,
+            }),
+            _ => Err(errors),
+        }
+    }
+
+    /// Build the tiles, objects and collision of the current map. Everything built here is marked
+    /// with `MapEntity`, so the map can be torn down and built again without touching the player.
+    fn build_map(&self, world: &mut World) {
+        type SystemData<'a> = (
+            Entities<'a>,
+            WriteStorage<'a, Transform>,
+            WriteStorage<'a, SpriteRender>,
+            WriteStorage<'a, AnimatedTile>,
+            WriteStorage<'a, TileChunk>,
+            WriteStorage<'a, Warp>,
+            WriteStorage<'a, MapEntity>,
+            WriteStorage<'a, ParallaxLayer>,
+            WriteStorage<'a, Parent>,
+            WriteStorage<'a, Tint>,
+            WriteStorage<'a, Transparent>,
+            Read<'a, AssetStorage<SpriteSheet>>,
+            ReadExpect<'a, Loader>,
+        );
+
+        let map_data = world.exec(
+        |(
+            entities,
+            mut transform_storage,
+            mut sprite_render_storage,
+            mut animated_tile_storage,
+            mut tile_chunk_storage,
+            mut warp_storage,
+            mut map_entity_storage,
+            mut parallax_layer_storage,
+            mut parent_storage,
+            mut tint_storage,
+            mut transparent_storage,
+            sprite_sheet_storage,
+            loader,
+        ): SystemData| {
+            let map = &self.map;
 
-            // Build the map
-            let map = map_storage.get(&self.map_handle.clone()).unwrap();
                 // Now, we need to loop over each tileset. A tileset is - here - the same as a generated spritesheet.
-                // Here, we reutrn `MapData`, which is just a struct wrapper for the tile data and spritesheet. In
-                // the future, we may want a hashmap with the tileset ID
-                let map_data: (Vec<(MapData)>) = map
+                // Here, we reutrn `MapData`, which is just a struct wrapper for the tile data and spritesheet,
+                // along with the first global tile ID of the tileset so we can find it again for each tile.
+                let map_data: Vec<MapData> = map
                     .tilesets
                     .iter()
-                    .map(|set| {
-                        let tile_data = TileData {
-                            tile_size: Vector2::new(set.tile_width as i32, set.tile_height as i32),
-                            tileset_size: Vector2::new(
-                                set.images[0].width.clone(),
-                                set.images[0].height.clone(),
-                            ),
-                        };
-
-                        // A place to store the tile sprites in. This is useful for animated tile sprites (which
-                        // we are not using at the moment)
+                    .zip(self.tilesets.iter())
+                    .map(|(set, texture)| {
+                        let tile_data = TileData::from_tileset(set);
+
+                        // A place to store the tile sprites in. Animated tiles swap between these by index.
                         let mut tile_sprites: Vec<Sprite> = Vec::with_capacity(1);
 
                         // Map our image to a texturecoordinates, so we can load the map directly without needing
-                        // a spritesheet ron
-                        for x in 0..tile_data.get_tileset_sprite_grid().rows {
-                            for y in 0..tile_data.get_tileset_sprite_grid().columns {
-                                // Coordinates of the 64x64 tile sprite inside the whole
-                                let offset = tile_data.get_tileset_offset_grid();
-                                let tex_coords = TextureCoordinates {
-                                    left: y as f32 * offset.columns,
-                                    right: (y + 1) as f32 * offset.columns,
-                                    top: x as f32 * offset.rows,
-                                    bottom: (x + 1) as f32 * offset.rows,
+                        // a spritesheet ron. Sprites are numbered by local tile ID, row by row.
+                        if let Some(tile_data) = tile_data.as_ref() {
+                            for id in 0..tile_data.get_tile_count() {
+                                let tex_coords = match tile_data.get_tile_coordinates(id) {
+                                    Some(tex_coords) => tex_coords,
+                                    None => break,
                                 };
 
                                 let sprite = Sprite {
-                                    width: tile_data.tile_size.x as f32,
-                                    height: tile_data.tile_size.y as f32,
+                                    width: tile_data.tile_size.0 as f32,
+                                    height: tile_data.tile_size.1 as f32,
                                     offsets: [0.0, 0.0],
                                     tex_coords,
                                 };
@@ -143,118 +412,355 @@ impl SimpleState for MainGameState {
                             }
                         }
 
-                        // The spritesheet containing all the sprites we calculated in this tileset
-                        let sprite_sheet = SpriteSheet {
-                            texture: self.texture_handle.clone(),
-                            sprites: tile_sprites,
-                        };
+                        // The spritesheet containing all the sprites we calculated in this tileset,
+                        // inserted into world resources for later use
+                        let sprite_sheet_handle = texture.as_ref().map(|texture| {
+                            let sprite_sheet = SpriteSheet {
+                                texture: texture.clone(),
+                                sprites: tile_sprites,
+                            };
+                            loader.load_from_data(sprite_sheet, (), &sprite_sheet_storage)
+                        });
+
+                        // Any tile in the set with an `<animation>` gets its frames turned into sprite
+                        // numbers, keyed by the tile's local ID
+                        let animations = set
+                            .tiles
+                            .iter()
+                            .filter_map(|tile| {
+                                tile.animation.as_ref().map(|frames| {
+                                    (tile.id, AnimatedTile {
+                                        frames: frames
+                                            .iter()
+                                            .map(|frame| (
+                                                frame.tile_id as usize,
+                                                Duration::from_millis(frame.duration as u64),
+                                            ))
+                                            .collect(),
+                                    })
+                                })
+                            })
+                            .collect();
+
+                        // Tiles with a `map` property warp the player when stepped on, wherever
+                        // they're placed
+                        let warps = set
+                            .tiles
+                            .iter()
+                            .filter_map(|tile| {
+                                objects::warp_target(&tile.properties).map(|target| (tile.id, target))
+                            })
+                            .collect();
 
-                        // Insert the sprite sheet, which consists of all the tile sprites,
-                        // into world resources for later use
                         MapData {
+                            first_gid: set.first_gid,
                             tile_data,
-                            sprite_sheet_handle: loader.load_from_data(
-                                sprite_sheet,
-                                (),
-                                &sprite_sheet_storage,
-                            ),
+                            animations,
+                            warps,
+                            sprite_sheet_handle,
                         }
                     })
                     .collect();
 
-                // Now that all the tile sprites/textures are loaded in
-                // we can start drawing the tiles for our viewing pleasure
+                // Every layer is grouped into chunks, and whole chunks are hidden when they're off
+                // screen instead of checking every tile
+                let chunk_layout = ChunkLayout {
+                    chunk_size: CHUNK_SIZE,
+                    tile_size: (map.tile_width as f32, map.tile_height as f32),
+                    origin: MAP_ORIGIN,
+                };
+
                 // Loop over every layer. Because the first layer should be
                 // last on the Z axis, we build in reverse.
                 for (i, layer) in map.layers.clone().iter().rev().enumerate() {
+                    // Collision layers only tell us where we can't walk, so we don't draw them
+                    if collision::is_collision_layer(layer) {
+                        continue;
+                    }
+                    let settings = LayerSettings::from_layer(layer);
+                    // Hidden layers are usually reference or unfinished work, so we leave them out
+                    if !settings.visible {
+                        continue;
+                    }
+                    // Every layer before the last (remember, this is reverse iterating)
+                    // should be further away, unless its `z` property puts it somewhere else.
+                    // Layers marked `above` go over the player instead.
+                    let z = settings.depth(i);
+                    // Parallax layers get an entity of their own, which their tiles are moved
+                    // around with
+                    let parallax_layer = if settings.has_parallax() {
+                        Some(entities
+                            .build_entity()
+                            .with(Transform::default(), &mut transform_storage)
+                            .with(ParallaxLayer { factor: settings.parallax }, &mut parallax_layer_storage)
+                            .with(MapEntity::default(), &mut map_entity_storage)
+                            .build())
+                    } else {
+                        None
+                    };
+                    // See-through layers need to be drawn by the transparent pass
+                    let tint = if settings.opacity < 1.0 {
+                        Some(Tint(Srgba::new(1.0, 1.0, 1.0, settings.opacity)))
+                    } else {
+                        None
+                    };
+                    // The tile entities in each chunk
+                    let mut chunks: HashMap<(usize, usize), Vec<Entity>> = HashMap::new();
+
                     // Loop the row first and then the individual tiles on that row
                     // and then switch to the next row
                     // i_row = row iteration number
                     // i_column = column iteration number
                     for (i_row, row) in layer.tiles.iter().enumerate().clone() {
-                        for (i_column, &tile) in row.iter().enumerate() {
+                        for (i_column, &gid) in row.iter().enumerate() {
+                            // Flipped tiles have flags in the top bits of their ID, which we need
+                            // to take off before looking the tile up
+                            let (tile, flip) = decode_gid(gid);
                             // Do nothing with empty tiles
                             if tile == 0 {
                                 continue;
                             }
 
-                            // Tile ids start from 1 but tileset sprites start from 0
-                            let tile = tile - 1;
-
-                            // Renderer for the tile
-                            let tile_renderer = SpriteRender {
-                                sprite_sheet: map_data[0].sprite_sheet_handle.clone(),
-                                sprite_number: tile as usize,
+                            // Find the tileset this tile belongs to. Global tile IDs start from the
+                            // tileset's `first_gid` but its sprites start from 0
+                            let tileset = match MapData::for_gid(&map_data, tile) {
+                                Some(tileset) => tileset,
+                                None => continue,
                             };
+                            let local_id = tile - tileset.first_gid;
+
+                            // Where we should draw the tile? Tilesets can have different tile sizes, so
+                            // we place tiles by the map's grid instead. Tiles are centered on their
+                            // cell.
+                            let center = Vector3::new(
+                                Float::from(MAP_ORIGIN.0 + (i_column as f32 + 0.5) * map.tile_width as f32 + settings.offset.0),
+                                Float::from(MAP_ORIGIN.1 - (i_row as f32 + 0.5) * map.tile_height as f32 + settings.offset.1),
+                                Float::from(z),
+                            );
+
+                            // Warp tiles only need an area, whether or not the tile is drawn
+                            if let Some(target) = tileset.warps.get(&local_id) {
+                                entities
+                                    .build_entity()
+                                    .with(Transform::from(center), &mut transform_storage)
+                                    .with(Warp {
+                                        target: target.clone(),
+                                        size: (map.tile_width as f32, map.tile_height as f32),
+                                    }, &mut warp_storage)
+                                    .with(MapEntity::default(), &mut map_entity_storage)
+                                    .build();
+                            }
 
-                            // Where we should draw the tile?
-                            let tile_transform = {
-                                // Bottom Left is 0,0 so we flip it to Top Left with the
-                                // ScreenDimensions.height since tiled coordinates start from top
-                                let coordinates = (
-                                    Float::from(
-                                        i_column as f32 * map_data[0].tile_data.tile_size.x as f32,
-                                    ),
-                                    Float::from(
-                                        1f32 - (i_row as f32
-                                            * map_data[0].tile_data.tile_size.y as f32),
-                                    ),
-                                    // Every layer before the last (remember, this is reverse iterating)
-                                    // should be further away. `i` is zero-indexed so we need to add one first.
-                                    Float::from(-10.0 * (i + 1) as f32),
-                                );
-                                // Offset the positions by half the tile size so they're nice and snuggly on the screen
-                                // Alternatively could use the Sprite offsets instead: [-32.0, 32.0]. Depends on the use case I guess.
-                                let offset = (
-                                    Float::from(map_data[0].tile_data.tile_size.x as f32 / 2.0),
-                                    Float::from(-map_data[0].tile_data.tile_size.y as f32 / 2.0),
-                                );
-                                Transform::from(Vector3::new(
-                                    offset.0 + coordinates.0,
-                                    offset.1 + coordinates.1,
-                                    coordinates.2,
-                                ))
+                            // Every tile is a sprite on its tileset's sheet, so the whole map shares
+                            // the tileset textures
+                            let sprite_sheet = match tileset.sprite_sheet_handle.as_ref() {
+                                Some(sprite_sheet) => sprite_sheet.clone(),
+                                None => continue,
                             };
-                            // Create the tile entity
-                            entities
+                            let mut tile_transform = Transform::from(center);
+                            // Sprites are centered on the transform, so flipping keeps the tile
+                            // in its cell
+                            flip.apply(&mut tile_transform);
+                            let tile_entity = entities
                                 .build_entity()
                                 .with(tile_transform, &mut transform_storage)
-                                .with(tile_renderer, &mut sprite_render_storage)
-                                .build();
+                                .with(SpriteRender {
+                                    sprite_sheet,
+                                    sprite_number: local_id as usize,
+                                }, &mut sprite_render_storage)
+                                .with(MapEntity::default(), &mut map_entity_storage);
+                            let tile_entity = match tileset.animations.get(&local_id) {
+                                Some(animation) => tile_entity.with(animation.clone(), &mut animated_tile_storage),
+                                None => tile_entity,
+                            };
+                            let tile_entity = match parallax_layer {
+                                Some(layer_entity) => tile_entity.with(Parent::new(layer_entity), &mut parent_storage),
+                                None => tile_entity,
+                            };
+                            let tile_entity = match tint.clone() {
+                                Some(tint) => tile_entity
+                                    .with(tint, &mut tint_storage)
+                                    .with(Transparent, &mut transparent_storage),
+                                None => tile_entity,
+                            };
+                            chunks
+                                .entry(chunk_layout.chunk_of(i_column, i_row))
+                                .or_insert_with(Vec::new)
+                                .push(tile_entity.build());
                         }
                     }
+
+                    // Parallax layers don't stay where the chunk bounds say they are, so their
+                    // tiles are always shown
+                    if parallax_layer.is_some() {
+                        continue;
+                    }
+                    // Every other chunk starts out visible, and the culling system hides
+                    // whatever isn't on screen
+                    for (chunk, tile_entities) in chunks.into_iter() {
+                        let bounds = chunk_layout
+                            .bounds(chunk)
+                            .translate(settings.offset.0, settings.offset.1);
+                        entities
+                            .build_entity()
+                            .with(Transform::from(Vector3::new(
+                                Float::from((bounds.left + bounds.right) / 2.0),
+                                Float::from((bounds.bottom + bounds.top) / 2.0),
+                                Float::from(z),
+                            )), &mut transform_storage)
+                            .with(TileChunk {
+                                tiles: tile_entities,
+                                bounds,
+                                visible: true,
+                            }, &mut tile_chunk_storage)
+                            .with(MapEntity::default(), &mut map_entity_storage)
+                            .build();
+                    }
                 }
+
+                map_data
         });
+
+        // Spawn everything placed in the map's object layers through the spawn registry
+        let map_objects: Vec<MapObject> = objects::objects(&self.map)
+            .filter(|object| object.obj_type != objects::PLAYER_SPAWN)
+            .map(|object| {
+                // Tile objects can be flipped just like tiles in a layer
+                let (gid, flip) = decode_gid(object.gid);
+                let mut transform = Transform::from(objects::object_translation(object));
+                flip.apply(&mut transform);
+                MapObject {
+                    object: object.clone(),
+                    transform,
+                    sprite: MapData::for_gid(&map_data, gid).and_then(|tileset| {
+                        tileset.sprite_sheet_handle.clone().map(|sprite_sheet| SpriteRender {
+                            sprite_sheet,
+                            sprite_number: (gid - tileset.first_gid) as usize,
+                        })
+                    }),
+                }
+            })
+            .collect();
+        let registry = world
+            .res
+            .entry::<SpawnRegistry>()
+            .or_insert_with(SpawnRegistry::default)
+            .clone();
+        for map_object in map_objects.iter() {
+            if let Some(entity) = registry.spawn(world, map_object) {
+                // Spawn functions don't know about map ownership, so we mark their entities here
+                world
+                    .write_storage::<MapEntity>()
+                    .insert(entity, MapEntity::default())
+                    .ok();
+            }
+        }
+
+        // Build the collision grid from the same map, so movement can refuse solid cells
+        world.add_resource(CollisionGrid::from_map(&self.map));
+        // The camera stays inside the map
+        world.add_resource(MapBounds::from_map(&self.map));
     }
+
+    /// Tear down everything built from the current map and build `map` in its place. If the old
+    /// map can't be torn down, it's kept and `map` is dropped.
+    fn rebuild_map(&mut self, world: &mut World, map: Map) -> Result<(), WrongGeneration> {
+        let old = map_entities(world);
+        world.delete_entities(&old)?;
+        self.map = map;
+        self.build_map(world);
+        Ok(())
+    }
+}
+
+/// Every entity built by `build_map`
+fn map_entities(world: &World) -> Vec<Entity> {
+    (&world.entities(), &world.read_storage::<MapEntity>())
+        .join()
+        .map(|(entity, _)| entity)
+        .collect()
 }
 
-struct TileData {
-    tile_size: Vector2<i32>,
-    tileset_size: Vector2<i32>,
+/// The asset behind `handle`, or `None` with an error added to `errors` if it isn't loaded.
+/// Assets that failed to load are reported by the load state long before this, so this only
+/// catches assets that were never asked for.
+fn loaded<A: Asset + Clone>(
+    world: &World,
+    handle: Option<Handle<A>>,
+    id: &str,
+    errors: &mut Vec<LoadError>,
+) -> Option<A> {
+    let handle = handle?;
+    let asset = world.read_resource::<AssetStorage<A>>().get(&handle).cloned();
+    if asset.is_none() {
+        errors.push(LoadError::new(id, format!("the {} is not loaded", A::NAME)));
+    }
+    asset
 }
 
-struct TileGrid<T> {
-    columns: T,
-    rows: T,
+/// The assets from the manifest that the player and camera are built from
+struct RequiredAssets {
+    camera_config: CameraConfig,
+    player_config: PlayerConfig,
+    player_sprites: Handle<SpriteSheet>,
+    player_animation: CharacterAnimationConfig,
 }
 
-impl TileData {
-    pub fn get_tileset_sprite_grid(&self) -> TileGrid<i32> {
-        TileGrid::<i32> {
-            columns: (self.tileset_size.x / self.tile_size.x) as i32,
-            rows: (self.tileset_size.y / self.tile_size.y) as i32,
+impl RequiredAssets {
+    /// Look every one up by its id, failing with all that are missing or not loaded
+    fn find(world: &World) -> Result<Self, Vec<LoadError>> {
+        let assets = world.res.try_fetch::<AssetHandles>().ok_or_else(|| {
+            vec![LoadError::new(MANIFEST_PATH, "the asset manifest was never loaded".to_string())]
+        })?;
+        let mut errors = Vec::new();
+        let camera_config = loaded(world, required(&assets, CAMERA_CONFIG, &mut errors), CAMERA_CONFIG, &mut errors);
+        let player_config = loaded(world, required(&assets, PLAYER_CONFIG, &mut errors), PLAYER_CONFIG, &mut errors);
+        let player_sprites = required(&assets, PLAYER_SPRITES, &mut errors);
+        let player_animation = loaded(world, required(&assets, PLAYER_ANIMATION, &mut errors), PLAYER_ANIMATION, &mut errors);
+        match (camera_config, player_config, player_sprites, player_animation) {
+            (Some(camera_config), Some(player_config), Some(player_sprites), Some(player_animation)) => Ok(Self {
+                camera_config,
+                player_config,
+                player_sprites,
+                player_animation,
+            }),
+            _ => Err(errors),
         }
     }
-    pub fn get_tileset_offset_grid(&self) -> TileGrid<f32> {
-        let grid = self.get_tileset_sprite_grid();
-        TileGrid::<f32> {
-            columns: 1.0 / grid.columns as f32,
-            rows: 1.0 / grid.rows as f32,
-        }
+}
+
+/// The handle stored under `id`, or `None` with an error added to `errors` if there isn't one of
+/// the right type
+fn required<A: Asset>(assets: &AssetHandles, id: &str, errors: &mut Vec<LoadError>) -> Option<Handle<A>> {
+    let handle = assets.get::<A>(id);
+    if handle.is_none() {
+        errors.push(LoadError::new(
+            id,
+            format!("the asset manifest has no {} by this id", A::NAME),
+        ));
     }
+    handle
 }
 
 struct MapData {
-    tile_data: TileData,
-    sprite_sheet_handle: SpriteSheetHandle,
+    first_gid: u32,
+    /// Where the tiles are in the tileset's image, if it has one
+    tile_data: Option<TileData>,
+    /// Animations of the tiles in this tileset, by local tile ID
+    animations: HashMap<u32, AnimatedTile>,
+    /// Where tiles in this tileset warp to, by local tile ID
+    warps: HashMap<u32, WarpTarget>,
+    /// Sprites of every tile, if the tileset has an image to cut them from
+    sprite_sheet_handle: Option<SpriteSheetHandle>,
+}
+
+impl MapData {
+    /// Find the tileset a global tile ID belongs to. That's the tileset with the highest
+    /// `first_gid` that is still less than or equal to the tile ID.
+    fn for_gid(map_data: &[MapData], gid: u32) -> Option<&MapData> {
+        map_data
+            .iter()
+            .filter(|data| data.first_gid <= gid)
+            .max_by_key(|data| data.first_gid)
+    }
 }
@@ -1,5 +1,7 @@
+mod error;
 mod main;
 mod load;
 
+pub use error::ErrorState;
 pub use main::MainGameState;
-pub use load::LoadMapState;
\ No newline at end of file
+pub use load::{LoadDataState, LoadMapState};
\ No newline at end of file
@@ -0,0 +1,59 @@
+use crate::{
+    components::{CharacterAnimation, GridMovement, Movement},
+    util::data::Direction,
+};
+
+use amethyst::core::{Time, Transform};
+use amethyst::ecs::{Join, Read, ReadStorage, System, WriteStorage};
+use amethyst::renderer::SpriteRender;
+
+/// Picks the sprite of every animated character from the direction it's moving in
+pub struct CharacterAnimationSystem {}
+
+impl Default for CharacterAnimationSystem {
+    fn default() -> Self {
+        Self {}
+    }
+}
+
+impl<'a> System<'a> for CharacterAnimationSystem {
+    type SystemData = (
+        WriteStorage<'a, CharacterAnimation>,
+        WriteStorage<'a, SpriteRender>,
+        ReadStorage<'a, Movement>,
+        ReadStorage<'a, GridMovement>,
+        ReadStorage<'a, Transform>,
+        Read<'a, Time>,
+    );
+
+    fn run(
+        &mut self,
+        (mut animations, mut sprite_renders, movements, grid_movements, transforms, time): Self::SystemData,
+    ) {
+        for (animation, sprite_render, movement, grid_movement, transform) in (
+            &mut animations,
+            &mut sprite_renders,
+            movements.maybe(),
+            grid_movements.maybe(),
+            &transforms,
+        )
+            .join()
+        {
+            // Free movement tells us its direction outright, while grid movement is only heading
+            // somewhere while it's partway through a step
+            let direction = if let Some(movement) = movement {
+                let direction = movement.direction();
+                Direction::from_vector(direction.x.as_f32(), direction.y.as_f32())
+            } else if let Some(grid_movement) = grid_movement.filter(|g| g.is_busy(transform)) {
+                let direction = grid_movement.target_local;
+                Direction::from_vector(direction.x.as_f32(), direction.y.as_f32())
+            } else {
+                None
+            };
+
+            if let Some(sprite_number) = animation.update(direction, time.delta_time()) {
+                sprite_render.sprite_number = sprite_number;
+            }
+        }
+    }
+}
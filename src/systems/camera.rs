@@ -0,0 +1,113 @@
+use crate::{
+    components::{clamp_center, CameraFollow, Movement},
+    util::data::{CameraConfig, CameraView, MapBounds},
+};
+
+use amethyst::core::{math::Vector3, Float, Time, Transform};
+use amethyst::ecs::{Entity, Join, Read, ReadStorage, System, Write, WriteStorage};
+use amethyst::renderer::camera::{Camera, Projection};
+use amethyst::window::ScreenDimensions;
+
+/// Fits the camera's projection to the window, following the scaling mode and zoom in the
+/// `CameraConfig` resource
+pub struct CameraScalingSystem {}
+
+impl Default for CameraScalingSystem {
+    fn default() -> Self {
+        Self {}
+    }
+}
+
+impl<'a> System<'a> for CameraScalingSystem {
+    type SystemData = (
+        WriteStorage<'a, Camera>,
+        Read<'a, CameraConfig>,
+        Option<Read<'a, ScreenDimensions>>,
+        Write<'a, CameraView>,
+    );
+
+    fn run(&mut self, (mut cameras, camera_config, screen, mut camera_view): Self::SystemData) {
+        let screen = screen.map(|screen| (screen.width(), screen.height()));
+        let view = CameraView::new(&camera_config, screen);
+        // New cameras are built with the right projection, so we only need to catch changes
+        if view == *camera_view {
+            return;
+        }
+        for camera in (&mut cameras).join() {
+            *camera = view.camera(&camera_config);
+        }
+        *camera_view = view;
+    }
+}
+
+/// Moves cameras towards the entity they follow, keeping them inside the map
+pub struct CameraFollowSystem {}
+
+impl Default for CameraFollowSystem {
+    fn default() -> Self {
+        Self {}
+    }
+}
+
+impl<'a> System<'a> for CameraFollowSystem {
+    type SystemData = (
+        ReadStorage<'a, CameraFollow>,
+        ReadStorage<'a, Movement>,
+        WriteStorage<'a, Transform>,
+        Read<'a, CameraView>,
+        Read<'a, MapBounds>,
+        Read<'a, Time>,
+    );
+
+    fn run(
+        &mut self,
+        (follows, movements, mut transforms, camera_view, map_bounds, time): Self::SystemData,
+    ) {
+        let half_size = camera_view.half_size;
+        let center_offset = camera_view.center_offset;
+
+        // Look up the targets first, since we can't read one transform while writing another
+        let targets: Vec<(Entity, (f32, f32), (f32, f32))> = follows
+            .join()
+            .filter_map(|follow| {
+                let target = transforms.get(follow.target)?.translation();
+                let velocity = movements
+                    .get(follow.target)
+                    .map(|movement| {
+                        let velocity = movement.velocity();
+                        (velocity.x.as_f32(), velocity.y.as_f32())
+                    })
+                    .unwrap_or((0.0, 0.0));
+                Some((follow.target, (target.x.as_f32(), target.y.as_f32()), velocity))
+            })
+            .collect();
+
+        for (follow, transform) in (&follows, &mut transforms).join() {
+            let (target, velocity) = match targets
+                .iter()
+                .find(|(entity, _, _)| *entity == follow.target)
+            {
+                Some((_, target, velocity)) => (*target, *velocity),
+                None => continue,
+            };
+
+            // The transform is the view's bottom left corner, but we want to follow with the center
+            let translation = transform.translation();
+            let center = (
+                translation.x.as_f32() + center_offset.0,
+                translation.y.as_f32() + center_offset.1,
+            );
+            let mut center = follow.next_center(center, target, velocity, time.delta_seconds());
+            if let Some(bounds) = map_bounds.0.as_ref() {
+                center = clamp_center(center, half_size, bounds);
+            }
+
+            let z = translation.z;
+            transform.set_translation(Vector3::new(
+                Float::from(camera_view.snap(center.0 - center_offset.0)),
+                Float::from(camera_view.snap(center.1 - center_offset.1)),
+                z,
+            ));
+        }
+    }
+}
@@ -0,0 +1,36 @@
+use crate::{
+    components::DepthSort,
+    util::{data::MapBounds, depth::sorted_z},
+};
+
+use amethyst::core::{math::Vector3, Float, Transform};
+use amethyst::ecs::{Join, Read, ReadStorage, System, WriteStorage};
+
+/// Sets the z of every depth sorted sprite from where its feet are on the map
+pub struct DepthSortSystem {}
+
+impl Default for DepthSortSystem {
+    fn default() -> Self {
+        Self {}
+    }
+}
+
+impl<'a> System<'a> for DepthSortSystem {
+    type SystemData = (
+        ReadStorage<'a, DepthSort>,
+        WriteStorage<'a, Transform>,
+        Read<'a, MapBounds>,
+    );
+
+    fn run(&mut self, (depth_sorts, mut transforms, map_bounds): Self::SystemData) {
+        let bounds = match map_bounds.0.as_ref() {
+            Some(bounds) => bounds,
+            None => return,
+        };
+        for (depth_sort, transform) in (&depth_sorts, &mut transforms).join() {
+            let translation = transform.translation().clone();
+            let z = sorted_z(translation.y.as_f32() - depth_sort.foot_offset, bounds);
+            transform.set_translation(Vector3::new(translation.x, translation.y, Float::from(z)));
+        }
+    }
+}
@@ -0,0 +1,104 @@
+use crate::{
+    components::CameraFollow,
+    util::{
+        data::{CameraConfig, GameBindings},
+        watch::{FileWatcher, HotReload},
+    },
+};
+
+use amethyst::config::Config;
+use amethyst::core::Time;
+use amethyst::ecs::{Join, Read, System, Write, WriteStorage};
+use amethyst::input::{Bindings, InputHandler};
+use log::{info, warn};
+
+use std::{path::PathBuf, time::Duration};
+
+/// How often the watched files are checked
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Watches the current map, `camera.ron` and `bindings.ron` for changes while developing. Config
+/// changes are applied straight away, while map changes are flagged in the `HotReload` resource
+/// for the running state to rebuild.
+pub struct HotReloadSystem {
+    watcher: FileWatcher,
+    assets_dir: PathBuf,
+    /// The map currently being watched, as an asset path
+    map: Option<String>,
+    next_poll: Duration,
+}
+
+impl HotReloadSystem {
+    pub fn new(assets_dir: PathBuf) -> Self {
+        let mut watcher = FileWatcher::default();
+        watcher.watch(assets_dir.join("config/camera.ron"));
+        watcher.watch(assets_dir.join("config/bindings.ron"));
+        Self {
+            watcher,
+            assets_dir,
+            map: None,
+            next_poll: Duration::default(),
+        }
+    }
+}
+
+impl<'a> System<'a> for HotReloadSystem {
+    type SystemData = (
+        Write<'a, HotReload>,
+        Write<'a, CameraConfig>,
+        WriteStorage<'a, CameraFollow>,
+        Write<'a, InputHandler<GameBindings>>,
+        Read<'a, Time>,
+    );
+
+    fn run(
+        &mut self,
+        (mut hot_reload, mut camera_config, mut follows, mut input_handler, time): Self::SystemData,
+    ) {
+        let now = time.absolute_real_time();
+        if now < self.next_poll {
+            return;
+        }
+        self.next_poll = now + POLL_INTERVAL;
+
+        // Follow the map around as warps switch between them
+        if hot_reload.map != self.map {
+            if let Some(old) = self.map.take() {
+                self.watcher.unwatch(&self.assets_dir.join(old));
+            }
+            if let Some(new) = hot_reload.map.clone() {
+                self.watcher.watch(self.assets_dir.join(&new));
+                self.map = Some(new);
+            }
+        }
+
+        for path in self.watcher.changed() {
+            if path == self.assets_dir.join("config/camera.ron") {
+                // A file that doesn't parse is most likely still being edited, so we keep
+                // what we have until it does
+                match CameraConfig::load_no_fallback(&path) {
+                    Ok(config) => {
+                        for follow in (&mut follows).join() {
+                            follow.deadzone = config.deadzone;
+                            follow.smoothing = config.smoothing;
+                            follow.look_ahead = config.look_ahead;
+                        }
+                        *camera_config = config;
+                        info!("Reloaded `{}`", path.display());
+                    }
+                    Err(error) => warn!("Could not reload `{}`: {}", path.display(), error),
+                }
+            } else if path == self.assets_dir.join("config/bindings.ron") {
+                match Bindings::<GameBindings>::load_no_fallback(&path) {
+                    Ok(bindings) => {
+                        input_handler.bindings = bindings;
+                        info!("Reloaded `{}`", path.display());
+                    }
+                    Err(error) => warn!("Could not reload `{}`: {}", path.display(), error),
+                }
+            } else {
+                hot_reload.map_changed = true;
+            }
+        }
+    }
+}
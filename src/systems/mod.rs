@@ -1,5 +1,19 @@
+mod animation;
+mod camera;
+mod depth;
+mod hot_reload;
 mod movement;
 mod player;
+mod rebind;
+mod tile;
+mod warp;
 
+pub use animation::CharacterAnimationSystem;
+pub use camera::{CameraFollowSystem, CameraScalingSystem};
+pub use depth::DepthSortSystem;
+pub use hot_reload::HotReloadSystem;
 pub use movement::{GridMovementSystem, MovementSystem};
 pub use player::PlayerSystem;
+pub use rebind::RebindSystem;
+pub use tile::{ChunkCullingSystem, ParallaxSystem, TileAnimationSystem};
+pub use warp::WarpSystem;
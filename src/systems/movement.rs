@@ -1,8 +1,11 @@
-use crate::components::{GridMovement, Movement};
+use crate::{
+    components::{GridMovement, Movement},
+    util::collision::CollisionGrid,
+};
 
 use amethyst::core::Time;
-use amethyst::core::{Float, Transform};
-use amethyst::ecs::{Join, Read, ReadStorage, System, WriteStorage};
+use amethyst::core::{math::Vector3, Float, Transform};
+use amethyst::ecs::{Join, Read, System, WriteStorage};
 
 
 pub struct GridMovementSystem {}
@@ -43,15 +46,131 @@ impl Default for MovementSystem {
 
 impl<'a> System<'a> for MovementSystem {
     type SystemData = (
-        ReadStorage<'a, Movement>,
+        WriteStorage<'a, Movement>,
         WriteStorage<'a, Transform>,
         Read<'a, Time>,
+        Read<'a, CollisionGrid>,
     );
 
-    fn run(&mut self, (movements, mut transforms, time): Self::SystemData) {
-        for (movement, transform) in (&movements, &mut transforms).join() {
-            // move at a constant speed in the direction
-            transform.append_translation(movement.next(&time));
+    fn run(&mut self, (mut movements, mut transforms, time, collision): Self::SystemData) {
+        for (movement, transform) in (&mut movements, &mut transforms).join() {
+            // speed up in the direction, or slow down or glide to the grid if we've stopped
+            let offset = movement.next(transform.translation(), &time);
+
+            // Refuse whichever part of the offset would push our feet into a solid cell
+            let translation = transform.translation();
+            let (x, y) = collision.slide(
+                (translation.x.as_f32(), translation.y.as_f32()),
+                (offset.x.as_f32(), offset.y.as_f32()),
+                (movement.feet.0.as_f32(), movement.feet.1.as_f32()),
+            );
+            transform.append_translation(Vector3::new(Float::from(x), Float::from(y), offset.z));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{components::SnapMode, util::data::MAP_ORIGIN};
+    use amethyst::ecs::{Builder, Entity, RunNow, World};
+
+    /// A world with a 4x4 grid of 32 pixel cells, where every frame takes exactly a 60th of a
+    /// second
+    fn world(solid: &[(usize, usize)]) -> World {
+        let mut world = World::new();
+        world.register::<Transform>();
+        world.register::<Movement>();
+        let mut time = Time::default();
+        time.set_delta_seconds(1.0 / 60.0);
+        world.add_resource(time);
+        let mut grid = CollisionGrid::new(4, 4, (32.0, 32.0), MAP_ORIGIN);
+        for &(column, row) in solid.iter() {
+            grid.set_solid(column, row, true);
         }
+        world.add_resource(grid);
+        world
+    }
+
+    fn mover(world: &mut World, x: f32, y: f32, snap_mode: SnapMode) -> Entity {
+        let mut movement = Movement::default();
+        movement.set_snap_mode(snap_mode);
+        movement.set_direction(Vector3::new(Float::from(1.), Float::from(0.), Float::from(0.)));
+        world
+            .create_entity()
+            .with(Transform::from(Vector3::new(Float::from(x), Float::from(y), Float::from(0.))))
+            .with(movement)
+            .build()
+    }
+
+    fn run(world: &World, frames: usize) {
+        let mut system = MovementSystem::default();
+        for _ in 0..frames {
+            system.run_now(&world.res);
+        }
+    }
+
+    fn position(world: &World, entity: Entity) -> (f32, f32) {
+        let transforms = world.read_storage::<Transform>();
+        let translation = transforms.get(entity).unwrap().translation();
+        (translation.x.as_f32(), translation.y.as_f32())
+    }
+
+    fn release(world: &World, entity: Entity) {
+        world
+            .write_storage::<Movement>()
+            .get_mut(entity)
+            .unwrap()
+            .set_direction(Vector3::zeros());
+    }
+
+    fn assert_at(actual: (f32, f32), expected: (f32, f32)) {
+        assert!(
+            (actual.0 - expected.0).abs() < 0.001 && (actual.1 - expected.1).abs() < 0.001,
+            "expected {:?}, got {:?}",
+            expected,
+            actual
+        );
+    }
+
+    #[test]
+    fn walking_into_a_wall_stops_flush_against_it() {
+        let mut world = world(&[(1, 0)]);
+        let player = mover(&mut world, 16.0, -15.0, SnapMode::None);
+        run(&world, 60);
+        // Our feet reach 12 pixels either side of us, and the wall starts at x = 32
+        assert_at(position(&world, player), (20.0, -15.0));
+    }
+
+    #[test]
+    fn one_tile_gaps_fit_without_lining_up_exactly() {
+        // A wall down the second column, with a gap in row 1
+        let mut world = world(&[(1, 0), (1, 2), (1, 3)]);
+        let player = mover(&mut world, 16.0, -45.0, SnapMode::None);
+        run(&world, 60);
+        let (x, y) = position(&world, player);
+        assert!(x > 64.0, "stopped at {} instead of going through the gap", x);
+        assert_eq!(y, -45.0);
+    }
+
+    #[test]
+    fn continuation_snap_comes_to_rest_on_the_next_tile() {
+        let mut world = world(&[]);
+        let player = mover(&mut world, 16.0, -15.0, SnapMode::Continuation);
+        run(&world, 6);
+        release(&world, player);
+        run(&world, 120);
+        assert_at(position(&world, player), (48.0, -15.0));
+    }
+
+    #[test]
+    fn nearest_snap_pulls_back_from_a_wall() {
+        // Pressed up against the wall, the middle of the tile we started on is the closest
+        let mut world = world(&[(1, 0)]);
+        let player = mover(&mut world, 16.0, -15.0, SnapMode::Nearest);
+        run(&world, 6);
+        release(&world, player);
+        run(&world, 120);
+        assert_at(position(&world, player), (16.0, -15.0));
     }
 }
@@ -1,6 +1,10 @@
 use crate::{
-    components::{Movement, Player},
-    util::data::{Action, ActionEvent},
+    components::{GridMovement, Movement, Player},
+    util::{
+        collision::CollisionGrid,
+        data::{Action, ActionEvent, GameBindings, MOVE_X_AXES, MOVE_Y_AXES},
+        input::{action_direction, InputConfig, InputState},
+    },
 };
 use amethyst::core::{
     math::{Unit, Vector3},
@@ -11,20 +15,22 @@ use amethyst::ecs::{
     Join, Read, ReadStorage, ReaderId, Resources, System, SystemData, Write,
     WriteStorage,
 };
-use amethyst::input::InputEvent;
+use amethyst::input::{InputEvent, InputHandler};
 use amethyst::shrev::EventChannel;
 
+use std::time::Duration;
+
 pub struct PlayerSystem {
     reader: Option<ReaderId<ActionEvent>>,
-    /// Unit vector that keeps track of keyboard movements
-    direction: Unit<Vector3<Float>>,
+    /// Keeps track of which keys are held, so opposing keys resolve properly
+    input: InputState,
 }
 
 impl Default for PlayerSystem {
     fn default() -> Self {
         Self {
             reader: None,
-            direction: Unit::new_unchecked(Vector3::zeros()),
+            input: InputState::default(),
         }
     }
 }
@@ -34,8 +40,12 @@ impl<'a> System<'a> for PlayerSystem {
         ReadStorage<'a, Player>,
         ReadStorage<'a, Transform>,
         WriteStorage<'a, Movement>,
+        WriteStorage<'a, GridMovement>,
         Write<'a, EventChannel<ActionEvent>>,
         Read<'a, Time>,
+        Read<'a, CollisionGrid>,
+        Read<'a, InputConfig>,
+        Read<'a, InputHandler<GameBindings>>,
     );
 
     fn setup(&mut self, res: &mut Resources) {
@@ -46,30 +56,95 @@ impl<'a> System<'a> for PlayerSystem {
         );
     }
 
-    fn run(&mut self, (players, _transforms, mut movements, events, _time): Self::SystemData) {
-        // add respective data to our movement/direction so our movement system can handle it properly
+    fn run(
+        &mut self,
+        (
+            players,
+            transforms,
+            mut movements,
+            mut grid_movements,
+            events,
+            time,
+            collision,
+            config,
+            input_handler,
+        ): Self::SystemData,
+    ) {
+        // Update our idea of what's held, then work out the direction from that as a whole
 
         for event in events.read(self.reader.as_mut().unwrap()) {
             match event {
-                InputEvent::ActionReleased(action) => match action {
-                    Action::Left => self.direction.as_mut_unchecked().x = Float::from(0f32),
-                    Action::Right => self.direction.as_mut_unchecked().x = Float::from(0f32),
-                    Action::Up => self.direction.as_mut_unchecked().y = Float::from(0f32),
-                    Action::Down => self.direction.as_mut_unchecked().y = Float::from(0f32),
-                },
-                InputEvent::ActionPressed(action) => match action {
-                    Action::Left => self.direction.as_mut_unchecked().x = Float::from(-1f32),
-                    Action::Right => self.direction.as_mut_unchecked().x = Float::from(1f32),
-                    Action::Up => self.direction.as_mut_unchecked().y = Float::from(1f32),
-                    Action::Down => self.direction.as_mut_unchecked().y = Float::from(-1f32),
-                },
-
+                InputEvent::ActionPressed(action) => {
+                    self.input.press(action.clone(), time.absolute_time())
+                }
+                InputEvent::ActionReleased(action) => self.input.release(action),
                 _ => (),
             }
         }
 
+        // Add any analog axes on top of the keys. Each axis is capped so that holding a key and
+        // tilting a stick the same way isn't any faster.
+        let (keys_x, keys_y) = self.input.direction(config.opposing_keys);
+        let axis_sum = |axes: &[&str], keys: f32| {
+            let sum = axes
+                .iter()
+                .filter_map(|axis| input_handler.axis_value(*axis))
+                .fold(keys, |sum, value| sum + value as f32);
+            sum.max(-1.0).min(1.0)
+        };
+        let x = axis_sum(&MOVE_X_AXES, keys_x);
+        let y = axis_sum(&MOVE_Y_AXES, keys_y);
+        let running = self.input.is_held(&Action::Run);
+
         for (_player, movement) in (&players, &mut movements).join() {
-            movement.set_direction(self.direction);
+            // Diagonals are normalised by the movement itself, and how far a stick is tilted
+            // decides how fast we go
+            movement.set_direction(Vector3::new(Float::from(x), Float::from(y), Float::from(0.)));
+            movement.set_running(running);
+        }
+
+        for (_player, grid_movement, transform) in
+            (&players, &mut grid_movements, &transforms).join()
+        {
+            grid_movement.set_running(running);
+            if grid_movement.is_busy(transform) {
+                continue;
+            }
+            // A buffered tap always moves us exactly one tile, even if the key was already
+            // released. Otherwise, keep stepping in whichever direction is still held.
+            let buffer = Duration::from_millis(config.buffer);
+            let action = match self.input.take_buffered(time.absolute_time(), buffer) {
+                Some(action) => action,
+                None => match self.input.last_direction(config.opposing_keys) {
+                    Some(action) => action,
+                    None => match step_from_axes(x, y) {
+                        Some(direction) => direction,
+                        None => continue,
+                    },
+                },
+            };
+            let (step_x, step_y) = match action_direction(&action) {
+                Some(direction) => direction,
+                None => continue,
+            };
+            let direction = Unit::new_unchecked(Vector3::new(
+                Float::from(step_x),
+                Float::from(step_y),
+                Float::from(0f32),
+            ));
+            grid_movement.set_move(&direction, transform, time.absolute_time(), &collision);
         }
     }
 }
+
+/// Grid movement can't go part way, so a stick steps us along whichever axis it's tilted
+/// furthest on, once it's past halfway
+fn step_from_axes(x: f32, y: f32) -> Option<Action> {
+    if x.abs() < 0.5 && y.abs() < 0.5 {
+        None
+    } else if x.abs() >= y.abs() {
+        Some(if x > 0.0 { Action::Right } else { Action::Left })
+    } else {
+        Some(if y > 0.0 { Action::Up } else { Action::Down })
+    }
+}
@@ -0,0 +1,83 @@
+use crate::util::{
+    bindings::{rebind, save_bindings, Rebind, RebindError, RebindOutcome},
+    data::{ActionEvent, GameBindings},
+};
+
+use amethyst::ecs::{Read, ReaderId, Resources, System, SystemData, Write};
+use amethyst::input::{InputEvent, InputHandler};
+use amethyst::shrev::EventChannel;
+
+use std::path::PathBuf;
+
+/// Captures the next button pressed while a rebind is requested through the `Rebind` resource,
+/// applies it to the input handler and writes the bindings back to disk
+pub struct RebindSystem {
+    reader: Option<ReaderId<ActionEvent>>,
+    /// Where the bindings are saved, which is the same file they were loaded from
+    path: PathBuf,
+}
+
+impl RebindSystem {
+    pub fn new(path: PathBuf) -> Self {
+        Self { reader: None, path }
+    }
+}
+
+impl<'a> System<'a> for RebindSystem {
+    type SystemData = (
+        Read<'a, EventChannel<ActionEvent>>,
+        Write<'a, InputHandler<GameBindings>>,
+        Write<'a, Rebind>,
+    );
+
+    fn setup(&mut self, res: &mut Resources) {
+        Self::SystemData::setup(res);
+        self.reader = Some(
+            res.fetch_mut::<EventChannel<ActionEvent>>()
+                .register_reader(),
+        );
+    }
+
+    fn run(&mut self, (events, mut input_handler, mut rebind_request): Self::SystemData) {
+        // Always read, so we don't pick up presses from before the rebind was requested
+        let button = events
+            .read(self.reader.as_mut().unwrap())
+            .filter_map(|event| match event {
+                InputEvent::ButtonPressed(button) => Some(button.clone()),
+                _ => None,
+            })
+            .next();
+
+        let button = match button {
+            Some(button) if rebind_request.is_capturing() => button,
+            _ => return,
+        };
+        let (action, replace_conflicts) = rebind_request.take_pending().unwrap();
+        let bindings = &mut input_handler.bindings;
+
+        match rebind(bindings, &action, button.clone(), replace_conflicts) {
+            Ok(()) => (),
+            Err(RebindError::Conflict(existing)) => {
+                rebind_request.outcome = Some(RebindOutcome::Conflict {
+                    action,
+                    button,
+                    existing,
+                });
+                return;
+            }
+            Err(RebindError::Rejected(reason)) => {
+                rebind_request.outcome = Some(RebindOutcome::Failed { action, reason });
+                return;
+            }
+        }
+
+        rebind_request.outcome = Some(match save_bindings(bindings, &self.path) {
+            Ok(()) => RebindOutcome::Bound { action, button },
+            Err(error) => RebindOutcome::NotSaved {
+                action,
+                button,
+                reason: error.to_string(),
+            },
+        });
+    }
+}
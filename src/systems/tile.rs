@@ -0,0 +1,123 @@
+use crate::{
+    components::{AnimatedTile, ParallaxLayer, TileChunk},
+    util::data::CameraView,
+};
+
+use amethyst::core::{math::Vector3, Float, Hidden, Time, Transform};
+use amethyst::ecs::{Join, Read, ReadStorage, System, WriteStorage};
+use amethyst::renderer::{camera::Camera, SpriteRender};
+
+/// Cycles the sprites of animated tiles
+pub struct TileAnimationSystem {}
+
+impl Default for TileAnimationSystem {
+    fn default() -> Self {
+        Self {}
+    }
+}
+
+impl<'a> System<'a> for TileAnimationSystem {
+    type SystemData = (
+        ReadStorage<'a, AnimatedTile>,
+        WriteStorage<'a, SpriteRender>,
+        Read<'a, Time>,
+    );
+
+    fn run(&mut self, (animated_tiles, mut sprite_renders, time): Self::SystemData) {
+        for (animated_tile, sprite_render) in (&animated_tiles, &mut sprite_renders).join() {
+            if let Some(sprite_number) = animated_tile.sprite_at(time.absolute_time()) {
+                sprite_render.sprite_number = sprite_number;
+            }
+        }
+    }
+}
+
+/// Hides the tiles of every chunk outside of the camera's view, and shows them again once they
+/// come back into view
+pub struct ChunkCullingSystem {}
+
+impl Default for ChunkCullingSystem {
+    fn default() -> Self {
+        Self {}
+    }
+}
+
+impl<'a> System<'a> for ChunkCullingSystem {
+    type SystemData = (
+        ReadStorage<'a, Camera>,
+        ReadStorage<'a, Transform>,
+        WriteStorage<'a, TileChunk>,
+        WriteStorage<'a, Hidden>,
+        Read<'a, CameraView>,
+    );
+
+    fn run(&mut self, (cameras, transforms, mut chunks, mut hiddens, camera_view): Self::SystemData) {
+        // The camera may be parented to something, so we need its global position
+        let camera_position = match (&cameras, &transforms).join().next() {
+            Some((_, transform)) => {
+                let matrix = transform.global_matrix();
+                (matrix[(0, 3)].as_f32(), matrix[(1, 3)].as_f32())
+            }
+            None => return,
+        };
+        let view = camera_view.view_bounds(camera_position);
+
+        for chunk in (&mut chunks).join() {
+            if !chunk.update_visibility(&view) {
+                continue;
+            }
+            for &tile in chunk.tiles.iter() {
+                if chunk.visible {
+                    hiddens.remove(tile);
+                } else {
+                    // The tile entities live as long as their chunk, so this can't fail
+                    hiddens.insert(tile, Hidden).ok();
+                }
+            }
+        }
+    }
+}
+
+/// Moves parallax layers along with the camera, so they scroll slower or faster than the map
+pub struct ParallaxSystem {}
+
+impl Default for ParallaxSystem {
+    fn default() -> Self {
+        Self {}
+    }
+}
+
+impl<'a> System<'a> for ParallaxSystem {
+    type SystemData = (
+        ReadStorage<'a, Camera>,
+        ReadStorage<'a, ParallaxLayer>,
+        WriteStorage<'a, Transform>,
+        Read<'a, CameraView>,
+    );
+
+    fn run(&mut self, (cameras, layers, mut transforms, camera_view): Self::SystemData) {
+        let camera_position = match (&cameras, &transforms).join().next() {
+            Some((_, transform)) => {
+                let translation = transform.translation();
+                (translation.x.as_f32(), translation.y.as_f32())
+            }
+            None => return,
+        };
+        // Layers line up with the map when the view is centered on the map's origin
+        let center = (
+            camera_position.0 + camera_view.center_offset.0,
+            camera_position.1 + camera_view.center_offset.1,
+        );
+
+        for (layer, transform) in (&layers, &mut transforms).join() {
+            // A layer that scrolls at `factor` of the map's speed makes up the difference by
+            // following the camera the rest of the way
+            let z = transform.translation().z;
+            transform.set_translation(Vector3::new(
+                Float::from(center.0 * (1.0 - layer.factor.0)),
+                Float::from(center.1 * (1.0 - layer.factor.1)),
+                z,
+            ));
+        }
+    }
+}
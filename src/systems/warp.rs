@@ -0,0 +1,51 @@
+use crate::{
+    components::{Player, Warp},
+    util::data::PendingWarp,
+};
+
+use amethyst::core::Transform;
+use amethyst::ecs::{Join, ReadStorage, System, Write};
+
+/// Requests a map change when the player steps onto a warp
+pub struct WarpSystem {
+    /// Whether the player was standing on a warp last frame. Warps only fire when stepped onto,
+    /// so arriving on top of one (say, coming back out of a door) doesn't send us right back.
+    on_warp: bool,
+}
+
+impl Default for WarpSystem {
+    fn default() -> Self {
+        Self { on_warp: true }
+    }
+}
+
+impl<'a> System<'a> for WarpSystem {
+    type SystemData = (
+        ReadStorage<'a, Player>,
+        ReadStorage<'a, Warp>,
+        ReadStorage<'a, Transform>,
+        Write<'a, PendingWarp>,
+    );
+
+    fn run(&mut self, (players, warps, transforms, mut pending_warp): Self::SystemData) {
+        let player = match (&players, &transforms).join().next() {
+            Some((_, transform)) => transform.translation(),
+            None => return,
+        };
+        let player = (player.x.as_f32(), player.y.as_f32());
+
+        let warp = (&warps, &transforms).join().find(|(warp, transform)| {
+            let center = transform.translation();
+            warp.contains((center.x.as_f32(), center.y.as_f32()), player)
+        });
+
+        match warp {
+            Some((warp, _)) if !self.on_warp => {
+                self.on_warp = true;
+                pending_warp.0 = Some(warp.target.clone());
+            }
+            Some(_) => (),
+            None => self.on_warp = false,
+        }
+    }
+}
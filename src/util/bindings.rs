@@ -0,0 +1,277 @@
+//! Loading, saving and changing key bindings while the game runs
+use crate::util::data::{Action, GameBindings};
+
+use amethyst::{
+    config::{Config, ConfigError},
+    input::{Axis, Bindings, Button},
+};
+use log::warn;
+use std::path::Path;
+
+/// Read bindings from a RON file. If the file is missing or corrupt, we fall back to the
+/// built in defaults so the game stays playable. The file is left alone, and gets replaced the
+/// next time a binding is changed.
+pub fn load_bindings(path: &Path) -> Bindings<GameBindings> {
+    match Bindings::<GameBindings>::load_no_fallback(path) {
+        Ok(bindings) => bindings,
+        Err(error) => {
+            warn!(
+                "Could not read bindings from `{}`, using the defaults instead: {}",
+                path.display(),
+                error
+            );
+            default_bindings()
+        }
+    }
+}
+
+/// Write bindings back out in the same format `load_bindings` reads
+pub fn save_bindings(bindings: &Bindings<GameBindings>, path: &Path) -> Result<(), ConfigError> {
+    bindings.write(path)
+}
+
+/// The bindings we ship with in `config/bindings.default.ron`. They're compiled in from that
+/// file, which rebinding never writes to, so they're still there when `bindings.ron` is missing
+/// or broken.
+pub fn default_bindings() -> Bindings<GameBindings> {
+    Bindings::load_bytes(DEFAULT_BINDINGS).expect("The shipped `config/bindings.default.ron` should always parse")
+}
+
+const DEFAULT_BINDINGS: &[u8] = include_bytes!("../../resources/config/bindings.default.ron");
+
+/// What a button is already bound to
+#[derive(Clone, Debug, PartialEq)]
+pub enum BoundTo {
+    Action(Action),
+    Axis(String),
+}
+
+/// The result of capturing a new button for an action
+#[derive(Clone, Debug)]
+pub enum RebindOutcome {
+    /// The button is now bound to the action, replacing its old binding on the same device
+    Bound { action: Action, button: Button },
+    /// The button is already used by another action or an axis, so nothing was changed
+    Conflict {
+        action: Action,
+        button: Button,
+        existing: BoundTo,
+    },
+    /// The binding was made, but couldn't be written to disk
+    NotSaved { action: Action, button: Button, reason: String },
+    /// The input system rejected the binding
+    Failed { action: Action, reason: String },
+}
+
+/// Resource used to ask for a rebind and to read how it went. Set `pending` through `request`,
+/// and the rebind system captures the next button pressed.
+#[derive(Default)]
+pub struct Rebind {
+    pending: Option<(Action, bool)>,
+    /// How the last rebind went. Cleared when a new one is requested.
+    pub outcome: Option<RebindOutcome>,
+}
+
+impl Rebind {
+    /// Capture the next button pressed for `action`
+    ///
+    /// * `replace_conflicts` - If the button is already bound to another action, take it away
+    /// from that action instead of reporting a conflict
+    pub fn request(&mut self, action: Action, replace_conflicts: bool) {
+        self.pending = Some((action, replace_conflicts));
+        self.outcome = None;
+    }
+
+    pub fn cancel(&mut self) {
+        self.pending = None;
+    }
+
+    pub fn is_capturing(&self) -> bool {
+        self.pending.is_some()
+    }
+
+    pub fn take_pending(&mut self) -> Option<(Action, bool)> {
+        self.pending.take()
+    }
+}
+
+/// Why a rebind left the bindings as they were
+#[derive(Clone, Debug, PartialEq)]
+pub enum RebindError {
+    /// The button is already bound elsewhere
+    Conflict(BoundTo),
+    /// The input system refused the binding
+    Rejected(String),
+}
+
+/// The other action or the axis that `button` is bound to, if any
+pub fn find_conflict(
+    bindings: &Bindings<GameBindings>,
+    action: &Action,
+    button: &Button,
+) -> Option<BoundTo> {
+    let action_conflict = bindings
+        .actions()
+        .filter(|other| *other != action)
+        .find(|other| {
+            bindings
+                .action_bindings(*other)
+                .any(|combo| combo.contains(button))
+        })
+        .cloned()
+        .map(BoundTo::Action);
+
+    action_conflict.or_else(|| {
+        bindings
+            .axes()
+            .find(|id| match bindings.axis_value(*id) {
+                Some(Axis::Emulated { pos, neg }) => pos == button || neg == button,
+                _ => false,
+            })
+            .cloned()
+            .map(BoundTo::Axis)
+    })
+}
+
+/// Bind `button` to `action`. Any binding the action already has on the same kind of device is
+/// removed first, so rebinding a key keeps the gamepad binding and the other way around.
+///
+/// * `replace_conflicts` - If the button is bound to another action, take it away from that
+/// action instead of failing. Buttons driving an axis are never taken, since the axis would
+/// lose one of its directions.
+///
+/// Either the whole rebind happens or the bindings are left exactly as they were.
+pub fn rebind(
+    bindings: &mut Bindings<GameBindings>,
+    action: &Action,
+    button: Button,
+    replace_conflicts: bool,
+) -> Result<(), RebindError> {
+    let conflict = find_conflict(bindings, action, &button);
+    match conflict {
+        Some(BoundTo::Action(_)) if replace_conflicts => (),
+        Some(existing) => return Err(RebindError::Conflict(existing)),
+        None => (),
+    }
+
+    let previous = bindings.clone();
+    if let Some(BoundTo::Action(other)) = conflict {
+        unbind(bindings, &other, &button);
+    }
+    let replaced: Vec<Vec<Button>> = bindings
+        .action_bindings(action)
+        .filter(|combo| combo.iter().any(|bound| same_device(bound, &button)))
+        .map(|combo| combo.to_vec())
+        .collect();
+    for combo in replaced.iter() {
+        bindings.remove_action_binding(action, combo);
+    }
+
+    bindings
+        .insert_action_binding(action.clone(), vec![button])
+        .map_err(|error| {
+            *bindings = previous;
+            RebindError::Rejected(format!("{:?}", error))
+        })
+}
+
+/// Remove `button` from every combo of `action`
+pub fn unbind(bindings: &mut Bindings<GameBindings>, action: &Action, button: &Button) {
+    let removed: Vec<Vec<Button>> = bindings
+        .action_bindings(action)
+        .filter(|combo| combo.contains(button))
+        .map(|combo| combo.to_vec())
+        .collect();
+    for combo in removed.iter() {
+        bindings.remove_action_binding(action, combo);
+    }
+}
+
+fn same_device(a: &Button, b: &Button) -> bool {
+    match (a, b) {
+        (Button::Key(_), Button::Key(_))
+        | (Button::Key(_), Button::ScanCode(_))
+        | (Button::ScanCode(_), Button::Key(_))
+        | (Button::ScanCode(_), Button::ScanCode(_)) => true,
+        (Button::Mouse(_), Button::Mouse(_)) => true,
+        (Button::Controller(a, _), Button::Controller(b, _)) => a == b,
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use amethyst::input::{ControllerButton, VirtualKeyCode};
+
+    fn bound(bindings: &Bindings<GameBindings>, action: &Action) -> Vec<Vec<Button>> {
+        bindings
+            .action_bindings(action)
+            .map(|combo| combo.to_vec())
+            .collect()
+    }
+
+    #[test]
+    fn defaults_come_from_the_shipped_file() {
+        let bindings = default_bindings();
+        assert_eq!(
+            bound(&bindings, &Action::Interact),
+            vec![
+                vec![Button::Key(VirtualKeyCode::Z)],
+                vec![Button::Key(VirtualKeyCode::Return)],
+                vec![Button::Controller(0, ControllerButton::A)],
+            ]
+        );
+        assert!(bindings.axis_value("keys_x").is_some());
+    }
+
+    #[test]
+    fn rebinding_a_key_keeps_the_gamepad_binding() {
+        let mut bindings = default_bindings();
+        rebind(&mut bindings, &Action::Run, Button::Key(VirtualKeyCode::C), false).unwrap();
+        let run = bound(&bindings, &Action::Run);
+        assert_eq!(run.len(), 2);
+        assert!(run.contains(&vec![Button::Controller(0, ControllerButton::X)]));
+        assert!(run.contains(&vec![Button::Key(VirtualKeyCode::C)]));
+    }
+
+    #[test]
+    fn conflicts_with_another_action_change_nothing() {
+        let mut bindings = default_bindings();
+        let result = rebind(&mut bindings, &Action::Run, Button::Key(VirtualKeyCode::Z), false);
+        assert_eq!(result, Err(RebindError::Conflict(BoundTo::Action(Action::Interact))));
+        assert_eq!(bound(&bindings, &Action::Run), bound(&default_bindings(), &Action::Run));
+        assert_eq!(
+            bound(&bindings, &Action::Interact),
+            bound(&default_bindings(), &Action::Interact)
+        );
+    }
+
+    #[test]
+    fn replacing_a_conflict_takes_the_button_from_the_other_action() {
+        let mut bindings = default_bindings();
+        rebind(&mut bindings, &Action::Run, Button::Key(VirtualKeyCode::Z), true).unwrap();
+        assert_eq!(
+            find_conflict(&bindings, &Action::Cancel, &Button::Key(VirtualKeyCode::Z)),
+            Some(BoundTo::Action(Action::Run))
+        );
+        assert!(!bound(&bindings, &Action::Interact).contains(&vec![Button::Key(VirtualKeyCode::Z)]));
+    }
+
+    #[test]
+    fn buttons_on_an_axis_are_never_taken() {
+        let mut bindings = default_bindings();
+        let button = Button::Key(VirtualKeyCode::D);
+        assert_eq!(
+            find_conflict(&bindings, &Action::Interact, &button),
+            Some(BoundTo::Axis("keys_x".to_string()))
+        );
+
+        let result = rebind(&mut bindings, &Action::Interact, button, true);
+        assert_eq!(result, Err(RebindError::Conflict(BoundTo::Axis("keys_x".to_string()))));
+        assert_eq!(
+            bound(&bindings, &Action::Interact),
+            bound(&default_bindings(), &Action::Interact)
+        );
+    }
+}
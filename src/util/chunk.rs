@@ -0,0 +1,124 @@
+//! Groups map tiles into fixed size chunks, so what's on screen is decided a chunk at a time
+//! instead of checking every tile.
+
+/// Width and height of a chunk, in tiles
+pub const CHUNK_SIZE: usize = 16;
+
+/// An axis aligned area in world coordinates
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Rect {
+    pub left: f32,
+    pub right: f32,
+    pub bottom: f32,
+    pub top: f32,
+}
+
+impl Rect {
+    /// Whether the two areas share any space. Areas that only touch at an edge don't overlap.
+    pub fn overlaps(&self, other: &Rect) -> bool {
+        self.left < other.right
+            && other.left < self.right
+            && self.bottom < other.top
+            && other.bottom < self.top
+    }
+
+    /// The same area, moved by `x` and `y`
+    pub fn translate(&self, x: f32, y: f32) -> Rect {
+        Rect {
+            left: self.left + x,
+            right: self.right + x,
+            bottom: self.bottom + y,
+            top: self.top + y,
+        }
+    }
+}
+
+/// Describes how tiles map to chunks and where those chunks are in the world
+#[derive(Clone, Copy, Debug)]
+pub struct ChunkLayout {
+    /// Width and height of a chunk, in tiles
+    pub chunk_size: usize,
+    /// Pixel width and height of a tile
+    pub tile_size: (f32, f32),
+    /// World position of the top left corner of tile (0, 0)
+    pub origin: (f32, f32),
+}
+
+impl ChunkLayout {
+    /// The chunk a tile belongs to, as a column and row of chunks
+    pub fn chunk_of(&self, column: usize, row: usize) -> (usize, usize) {
+        (column / self.chunk_size, row / self.chunk_size)
+    }
+
+    /// Column and row of a tile inside of its chunk
+    pub fn cell_in_chunk(&self, column: usize, row: usize) -> (usize, usize) {
+        (column % self.chunk_size, row % self.chunk_size)
+    }
+
+    /// The world area covered by a chunk
+    pub fn bounds(&self, chunk: (usize, usize)) -> Rect {
+        let width = self.chunk_size as f32 * self.tile_size.0;
+        let height = self.chunk_size as f32 * self.tile_size.1;
+        Rect {
+            left: self.origin.0 + chunk.0 as f32 * width,
+            right: self.origin.0 + (chunk.0 + 1) as f32 * width,
+            bottom: self.origin.1 - (chunk.1 + 1) as f32 * height,
+            top: self.origin.1 - chunk.1 as f32 * height,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::data::MAP_ORIGIN;
+
+    fn layout() -> ChunkLayout {
+        ChunkLayout {
+            chunk_size: 4,
+            tile_size: (2.0, 2.0),
+            origin: MAP_ORIGIN,
+        }
+    }
+
+    #[test]
+    fn tiles_belong_to_the_chunk_they_fall_in() {
+        let layout = layout();
+        assert_eq!(layout.chunk_of(0, 0), (0, 0));
+        assert_eq!(layout.chunk_of(3, 3), (0, 0));
+        assert_eq!(layout.chunk_of(4, 3), (1, 0));
+        assert_eq!(layout.chunk_of(9, 12), (2, 3));
+        assert_eq!(layout.cell_in_chunk(9, 12), (1, 0));
+    }
+
+    #[test]
+    fn chunk_bounds_run_down_from_the_origin() {
+        let layout = layout();
+        assert_eq!(
+            layout.bounds((1, 2)),
+            Rect {
+                left: 8.0,
+                right: 16.0,
+                bottom: -23.0,
+                top: -15.0,
+            }
+        );
+    }
+
+    #[test]
+    fn chunks_are_visible_when_they_overlap_the_view() {
+        let layout = layout();
+        let view = Rect {
+            left: 10.0,
+            right: 20.0,
+            bottom: -10.0,
+            top: 0.0,
+        };
+        assert!(layout.bounds((1, 0)).overlaps(&view));
+        assert!(layout.bounds((2, 1)).overlaps(&view));
+        assert!(!layout.bounds((0, 0)).overlaps(&view));
+        assert!(!layout.bounds((1, 2)).overlaps(&view));
+        // Only touching the edge of the view isn't enough
+        assert!(!layout.bounds((3, 0)).overlaps(&view.translate(4.0, 0.0)));
+    }
+}
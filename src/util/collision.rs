@@ -0,0 +1,352 @@
+//! Tile based collision. The grid is built once from the loaded map and kept as a resource, so
+//! movement systems can ask whether an area is passable without knowing anything about Tiled.
+use crate::util::{data::MAP_ORIGIN, flip::decode_gid};
+
+use std::collections::HashSet;
+use tiled::{Layer, Map, PropertyValue, Tile};
+
+/// Name of the layer whose every non-empty tile is solid
+pub const COLLISION_LAYER: &str = "collision";
+/// Boolean property that marks either a whole layer, or a single tile in a tileset, as solid
+pub const COLLIDES_PROPERTY: &str = "collides";
+
+/// Small inset applied to areas so that touching the edge of a solid cell doesn't count as
+/// overlapping it.
+const EPSILON: f32 = 0.001;
+
+/// A solid/passable grid in the same layout as our map. Cells are indexed by column and row,
+/// with row 0 at the top, just like Tiled.
+#[derive(Clone, Debug, Default)]
+pub struct CollisionGrid {
+    columns: usize,
+    rows: usize,
+    /// Pixel width and height of a single cell
+    tile_size: (f32, f32),
+    /// World position of the top left corner of cell (0, 0)
+    origin: (f32, f32),
+    solid: Vec<bool>,
+}
+
+impl CollisionGrid {
+    /// Create a fully passable grid
+    pub fn new(columns: usize, rows: usize, tile_size: (f32, f32), origin: (f32, f32)) -> Self {
+        Self {
+            columns,
+            rows,
+            tile_size,
+            origin,
+            solid: vec![false; columns * rows],
+        }
+    }
+
+    /// Build the grid from a map. A cell is solid if it has a tile on a collision layer, or if
+    /// any layer places a tile there that has the `collides` property set or collision shapes
+    /// drawn on it in Tiled's collision editor. The grid is no finer than a cell, so any shape
+    /// makes the whole cell solid.
+    pub fn from_map(map: &Map) -> Self {
+        let mut grid = Self::new(
+            map.width as usize,
+            map.height as usize,
+            (map.tile_width as f32, map.tile_height as f32),
+            MAP_ORIGIN,
+        );
+
+        // Collect every global tile ID that is solid on its own
+        let solid_tiles: HashSet<u32> = map
+            .tilesets
+            .iter()
+            .flat_map(|set| {
+                set.tiles
+                    .iter()
+                    .filter(|tile| {
+                        collides(tile.properties.get(COLLIDES_PROPERTY)) || has_collision_shapes(tile)
+                    })
+                    .map(move |tile| set.first_gid + tile.id)
+            })
+            .collect();
+
+        for layer in map.layers.iter() {
+            let whole_layer = is_collision_layer(layer);
+            for (row, tiles) in layer.tiles.iter().enumerate() {
+                for (column, &gid) in tiles.iter().enumerate() {
+                    // A flipped tile is just as solid as the original
+                    let (tile, _) = decode_gid(gid);
+                    if tile == 0 {
+                        continue;
+                    }
+                    if whole_layer || solid_tiles.contains(&tile) {
+                        grid.set_solid(column, row, true);
+                    }
+                }
+            }
+        }
+
+        grid
+    }
+
+    pub fn columns(&self) -> usize {
+        self.columns
+    }
+
+    pub fn rows(&self) -> usize {
+        self.rows
+    }
+
+    pub fn set_solid(&mut self, column: usize, row: usize, solid: bool) {
+        if column < self.columns && row < self.rows {
+            self.solid[row * self.columns + column] = solid;
+        }
+    }
+
+    /// Whether a cell is solid. Cells outside of the grid are always passable.
+    pub fn is_solid(&self, column: i32, row: i32) -> bool {
+        if column < 0 || row < 0 {
+            return false;
+        }
+        let (column, row) = (column as usize, row as usize);
+        column < self.columns && row < self.rows && self.solid[row * self.columns + column]
+    }
+
+    /// The cell containing a world position
+    pub fn cell_at(&self, x: f32, y: f32) -> (i32, i32) {
+        (
+            ((x - self.origin.0) / self.tile_size.0).floor() as i32,
+            ((self.origin.1 - y) / self.tile_size.1).floor() as i32,
+        )
+    }
+
+    /// World position of the middle of a cell
+    pub fn cell_center(&self, column: i32, row: i32) -> (f32, f32) {
+        (
+            self.origin.0 + (column as f32 + 0.5) * self.tile_size.0,
+            self.origin.1 - (row as f32 + 0.5) * self.tile_size.1,
+        )
+    }
+
+    /// Whether a world position lies inside a solid cell
+    pub fn is_point_blocked(&self, x: f32, y: f32) -> bool {
+        let (column, row) = self.cell_at(x, y);
+        self.is_solid(column, row)
+    }
+
+    /// Whether any part of an axis aligned world area overlaps a solid cell
+    ///
+    /// * `min` - bottom left corner of the area
+    /// * `max` - top right corner of the area
+    pub fn is_area_blocked(&self, min: (f32, f32), max: (f32, f32)) -> bool {
+        // `cell_at` flips y, so the top right corner gives us the smallest row
+        let (first_column, first_row) = self.cell_at(min.0 + EPSILON, max.1 - EPSILON);
+        let (last_column, last_row) = self.cell_at(max.0 - EPSILON, min.1 + EPSILON);
+        (first_row..=last_row)
+            .any(|row| (first_column..=last_column).any(|column| self.is_solid(column, row)))
+    }
+
+    /// Move a box by `offset`, stopping the x and y parts of the movement separately where the
+    /// box meets a solid cell, so that running diagonally into a wall slides along it instead of
+    /// stopping dead. The box ends up flush against the wall rather than short of it. Returns
+    /// the offset that can actually be applied.
+    ///
+    /// * `position` - center of the box
+    /// * `offset` - the movement we'd like to make
+    /// * `half_extents` - half the width and height of the box
+    pub fn slide(
+        &self,
+        position: (f32, f32),
+        offset: (f32, f32),
+        half_extents: (f32, f32),
+    ) -> (f32, f32) {
+        let blocked = |x: f32, y: f32| {
+            self.is_area_blocked(
+                (x - half_extents.0, y - half_extents.1),
+                (x + half_extents.0, y + half_extents.1),
+            )
+        };
+
+        // If we're already stuck inside something, let us move so we can get back out
+        if blocked(position.0, position.1) {
+            return offset;
+        }
+
+        // Columns count up with x, while rows count up as y goes down
+        let (_, rows) = self.spanned_cells(position, half_extents);
+        let leading_x = if offset.0 > 0.0 {
+            position.0 + half_extents.0
+        } else {
+            position.0 - half_extents.0
+        };
+        let x = clamp_axis(
+            (leading_x - self.origin.0) / self.tile_size.0,
+            offset.0 / self.tile_size.0,
+            EPSILON / self.tile_size.0,
+            |column| (rows.0..=rows.1).any(|row| self.is_solid(column, row)),
+        ) * self.tile_size.0;
+
+        let (columns, _) = self.spanned_cells((position.0 + x, position.1), half_extents);
+        let leading_y = if offset.1 > 0.0 {
+            position.1 + half_extents.1
+        } else {
+            position.1 - half_extents.1
+        };
+        let y = -clamp_axis(
+            (self.origin.1 - leading_y) / self.tile_size.1,
+            -offset.1 / self.tile_size.1,
+            EPSILON / self.tile_size.1,
+            |row| (columns.0..=columns.1).any(|column| self.is_solid(column, row)),
+        ) * self.tile_size.1;
+        (x, y)
+    }
+
+    /// The first and last column, and the first and last row, that a box overlaps
+    fn spanned_cells(&self, position: (f32, f32), half_extents: (f32, f32)) -> ((i32, i32), (i32, i32)) {
+        let (first_column, first_row) =
+            self.cell_at(position.0 - half_extents.0 + EPSILON, position.1 + half_extents.1 - EPSILON);
+        let (last_column, last_row) =
+            self.cell_at(position.0 + half_extents.0 - EPSILON, position.1 - half_extents.1 + EPSILON);
+        ((first_column, last_column), (first_row, last_row))
+    }
+}
+
+/// How far an edge can move along one axis of the grid before it enters a solid cell. Everything
+/// is measured in cells, counting up along the axis.
+///
+/// * `leading` - position of the edge facing the way we're moving
+/// * `offset` - how far we'd like to move
+/// * `is_solid` - whether the cell at this index along the axis blocks us anywhere across the
+/// width of the box
+fn clamp_axis<F: Fn(i32) -> bool>(leading: f32, offset: f32, epsilon: f32, is_solid: F) -> f32 {
+    if offset > 0.0 {
+        // Cells ahead of us start at their own index
+        let mut cell = (leading - epsilon).ceil() as i32;
+        while (cell as f32) < leading + offset - epsilon {
+            if is_solid(cell) {
+                return (cell as f32 - leading).max(0.0);
+            }
+            cell += 1;
+        }
+    } else if offset < 0.0 {
+        // Cells behind us end where the next one starts
+        let mut cell = (leading + epsilon).floor() as i32 - 1;
+        while ((cell + 1) as f32) > leading + offset + epsilon {
+            if is_solid(cell) {
+                return ((cell + 1) as f32 - leading).min(0.0);
+            }
+            cell -= 1;
+        }
+    }
+    offset
+}
+
+/// Layers named `collision`, or with the `collides` property set, only describe solid cells
+pub fn is_collision_layer(layer: &Layer) -> bool {
+    layer.name == COLLISION_LAYER || collides(layer.properties.get(COLLIDES_PROPERTY))
+}
+
+fn collides(property: Option<&PropertyValue>) -> bool {
+    match property {
+        Some(PropertyValue::BoolValue(value)) => *value,
+        _ => false,
+    }
+}
+
+/// Whether a tile has any shapes drawn in Tiled's collision editor
+fn has_collision_shapes(tile: &Tile) -> bool {
+    tile.objectgroup
+        .as_ref()
+        .map_or(false, |group| !group.objects.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A 4x4 grid of 32 pixel cells, laid out like a map
+    fn grid(solid: &[(usize, usize)]) -> CollisionGrid {
+        let mut grid = CollisionGrid::new(4, 4, (32.0, 32.0), MAP_ORIGIN);
+        for &(column, row) in solid.iter() {
+            grid.set_solid(column, row, true);
+        }
+        grid
+    }
+
+    /// Half the size of a box just smaller than a cell
+    const HALF: (f32, f32) = (15.0, 15.0);
+
+    #[test]
+    fn cells_are_counted_down_from_the_origin() {
+        let grid = grid(&[]);
+        assert_eq!(grid.cell_at(16.0, -15.0), (0, 0));
+        assert_eq!(grid.cell_at(0.0, 1.0), (0, 0));
+        assert_eq!(grid.cell_at(40.0, -40.0), (1, 1));
+        assert_eq!(grid.cell_at(127.0, -126.0), (3, 3));
+        assert_eq!(grid.cell_at(-1.0, 0.0), (-1, 0));
+        assert_eq!(grid.cell_at(10.0, 2.0), (0, -1));
+        assert_eq!(grid.cell_center(0, 0), (16.0, -15.0));
+        assert_eq!(grid.cell_center(2, 1), (80.0, -47.0));
+    }
+
+    #[test]
+    fn areas_are_blocked_by_any_solid_cell_they_overlap() {
+        let grid = grid(&[(1, 1)]);
+        assert!(grid.is_point_blocked(48.0, -47.0));
+        assert!(grid.is_area_blocked((32.0, -63.0), (64.0, -31.0)));
+        assert!(grid.is_area_blocked((0.0, -50.0), (40.0, -40.0)));
+        // Touching the edge of a solid cell isn't overlapping it
+        assert!(!grid.is_area_blocked((0.0, -63.0), (32.0, -31.0)));
+        assert!(!grid.is_area_blocked((32.0, -31.0), (64.0, 1.0)));
+    }
+
+    #[test]
+    fn cells_outside_of_the_grid_are_passable() {
+        let grid = grid(&[(0, 0), (3, 3)]);
+        assert!(!grid.is_solid(-1, 0));
+        assert!(!grid.is_solid(0, -1));
+        assert!(!grid.is_solid(4, 3));
+        assert!(!grid.is_solid(3, 4));
+        assert!(!grid.is_area_blocked((-64.0, 1.0), (-1.0, 64.0)));
+        // Walking off the edge of the map isn't stopped by the grid
+        assert_eq!(grid.slide((112.0, -15.0), (40.0, 0.0), HALF), (40.0, 0.0));
+        assert_eq!(grid.slide((16.0, 40.0), (-40.0, 0.0), HALF), (-40.0, 0.0));
+    }
+
+    #[test]
+    fn moving_diagonally_into_a_wall_slides_along_it() {
+        // A wall down the third column
+        let grid = grid(&[(2, 0), (2, 1), (2, 2), (2, 3)]);
+        // The box's right edge is 1 pixel from the wall
+        assert_eq!(grid.slide((48.0, -47.0), (10.0, 5.0), HALF), (1.0, 5.0));
+        assert_eq!(grid.slide((48.0, -47.0), (10.0, -5.0), HALF), (1.0, -5.0));
+        // Moving away from the wall isn't affected
+        assert_eq!(grid.slide((48.0, -47.0), (-10.0, 5.0), HALF), (-10.0, 5.0));
+    }
+
+    #[test]
+    fn moving_into_a_corner_stops() {
+        // Walls to the right and above
+        let grid = grid(&[(2, 1), (1, 0)]);
+        assert_eq!(grid.slide((48.0, -47.0), (10.0, 10.0), HALF), (1.0, 1.0));
+        // Either wall on its own can still be slid along
+        assert_eq!(grid.slide((48.0, -47.0), (10.0, -10.0), HALF), (1.0, -10.0));
+        assert_eq!(grid.slide((48.0, -47.0), (-10.0, 10.0), HALF), (-10.0, 1.0));
+    }
+
+    #[test]
+    fn boxes_stop_flush_against_walls() {
+        // Walls on every side of cell (1, 1)
+        let grid = grid(&[(0, 1), (2, 1), (1, 0), (1, 2)]);
+        let position = (47.5, -46.5);
+        assert_eq!(grid.slide(position, (8.0, 0.0), HALF), (1.5, 0.0));
+        assert_eq!(grid.slide(position, (-8.0, 0.0), HALF), (-0.5, 0.0));
+        assert_eq!(grid.slide(position, (0.0, 8.0), HALF), (0.0, 0.5));
+        assert_eq!(grid.slide(position, (0.0, -8.0), HALF), (0.0, -1.5));
+        // Already touching, so there's nowhere left to go
+        assert_eq!(grid.slide((49.0, -47.0), (4.0, 0.0), HALF), (0.0, 0.0));
+        // Walls further away than the offset don't get in the way
+        assert_eq!(grid.slide(position, (1.0, 0.0), HALF), (1.0, 0.0));
+    }
+
+    #[test]
+    fn boxes_stuck_inside_a_wall_can_move_out() {
+        let grid = grid(&[(1, 1)]);
+        assert_eq!(grid.slide((48.0, -47.0), (-20.0, 0.0), HALF), (-20.0, 0.0));
+    }
+}
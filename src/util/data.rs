@@ -4,9 +4,15 @@ use amethyst::{
     assets::{Handle, Asset, ProcessingState},
     error::Error,
     ecs::VecStorage,
+    renderer::camera::{Camera, Projection},
+};
+use crate::{
+    components::SnapMode,
+    util::{chunk::Rect, input::InputConfig},
 };
 use serde::{Deserialize, Serialize};
-use std::fmt;
+use std::{collections::HashMap, fmt};
+use tiled::Map;
 
 /// All available action keys in the game
 #[derive(Debug, Hash, Clone, Eq, PartialEq, Serialize, Deserialize)]
@@ -15,10 +21,24 @@ pub enum Action {
     Down,
     Left,
     Right,
+    /// Talk to or use whatever we're facing
+    Interact,
+    /// Back out of a dialogue or menu
+    Cancel,
+    /// Held to move faster
+    Run,
+    /// Open the game menu
+    Menu,
 }
 
+/// Analog axes, as named in `bindings.ron`, that move us horizontally. Their values are added to
+/// the direction from the Up, Down, Left and Right actions.
+pub const MOVE_X_AXES: [&str; 2] = ["move_x", "keys_x"];
+/// Analog axes, as named in `bindings.ron`, that move us vertically
+pub const MOVE_Y_AXES: [&str; 2] = ["move_y", "keys_y"];
+
 /// All cardinal and diagonal directions
-#[derive(Debug, Hash, Clone, Eq, PartialEq, Serialize, Deserialize)]
+#[derive(Debug, Hash, Clone, Copy, Eq, PartialEq, Serialize, Deserialize)]
 pub enum Direction {
     North,
     NorthEast,
@@ -30,6 +50,38 @@ pub enum Direction {
     NorthWest,
 }
 
+impl Direction {
+    /// The direction closest to a movement vector, with y pointing up. A zero vector has no
+    /// direction.
+    pub fn from_vector(x: f32, y: f32) -> Option<Direction> {
+        if x == 0.0 && y == 0.0 {
+            return None;
+        }
+        // Split the circle into eight slices, starting with East centered on 0 radians
+        let slice = (y.atan2(x) / std::f32::consts::FRAC_PI_4).round() as i32;
+        Some(match (slice % 8 + 8) % 8 {
+            0 => Direction::East,
+            1 => Direction::NorthEast,
+            2 => Direction::North,
+            3 => Direction::NorthWest,
+            4 => Direction::West,
+            5 => Direction::SouthWest,
+            6 => Direction::South,
+            _ => Direction::SouthEast,
+        })
+    }
+
+    /// The cardinal direction to use for a diagonal when there's nothing specific to it, such as
+    /// a sprite sheet with only four facings. Diagonals favour the horizontal direction.
+    pub fn cardinal(self) -> Direction {
+        match self {
+            Direction::NorthEast | Direction::SouthEast => Direction::East,
+            Direction::NorthWest | Direction::SouthWest => Direction::West,
+            direction => direction,
+        }
+    }
+}
+
 impl fmt::Display for Action {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(f, "{}", format!("{:?}", *self))
@@ -44,12 +96,62 @@ impl BindingTypes for GameBindings {
 
 pub type ActionEvent = InputEvent<Action>;
 
+/// How the camera's view is fit to the window
+///
+/// * `PixelPerfect` - scale by a whole number of screen pixels per world pixel, the largest that
+/// fits the configured size, and show more of the world to fill the rest of the window
+/// * `Fit` - scale as far as fits the configured size while keeping its aspect ratio, showing more
+/// of the world along the longer side of the window
+/// * `Stretch` - always show exactly the configured size, stretched over the whole window
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub enum ScalingMode {
+    PixelPerfect,
+    Fit,
+    Stretch,
+}
+
+impl Default for ScalingMode {
+    fn default() -> Self {
+        ScalingMode::Stretch
+    }
+}
+
 #[derive(Serialize, Deserialize, Clone, Debug, Default)]
 pub struct CameraConfig {
     pub origin: (usize, usize),
     pub size: (usize, usize),
     pub znear: f32,
     pub zfar: f32,
+    /// Half the width and height of the box around the center of the view that the player can
+    /// move in without the camera following
+    #[serde(default)]
+    pub deadzone: (f32, f32),
+    /// How quickly the camera catches up with the player, per second. Zero snaps to the player.
+    #[serde(default)]
+    pub smoothing: f32,
+    /// Seconds of the player's velocity to look ahead by
+    #[serde(default)]
+    pub look_ahead: f32,
+    #[serde(default)]
+    pub scaling: ScalingMode,
+    /// How far the camera is zoomed in, where 2 shows half as much of the world. Change it on the
+    /// `CameraConfig` resource to zoom while the game runs.
+    #[serde(default = "default_zoom")]
+    pub zoom: f32,
+}
+
+fn default_zoom() -> f32 {
+    1.0
+}
+
+impl CameraConfig {
+    /// How far the center of the view is from the camera's position
+    pub fn center_offset(&self) -> (f32, f32) {
+        (
+            (self.origin.0 as f32 + self.size.0 as f32) / 2.0,
+            (self.origin.1 as f32 + self.size.1 as f32) / 2.0,
+        )
+    }
 }
 
 impl Asset for CameraConfig {
@@ -64,3 +166,203 @@ impl From<CameraConfig> for Result<ProcessingState<CameraConfig>, Error> {
             Ok(ProcessingState::Loaded(camera_config))
         }
 }
+
+/// What the camera actually shows, worked out from its config and the size of the window. The
+/// camera scaling system keeps this up to date as the window is resized.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct CameraView {
+    /// Center of the view relative to the camera's position
+    pub center_offset: (f32, f32),
+    /// Half the width and height of the view in world units
+    pub half_size: (f32, f32),
+    /// World units per screen pixel, if the camera should only ever move by whole screen pixels
+    pub pixel_snap: Option<f32>,
+}
+
+impl CameraView {
+    /// * `screen` - width and height of the window in pixels. Without one, such as when running
+    /// headless, the configured size is used as is.
+    pub fn new(config: &CameraConfig, screen: Option<(f32, f32)>) -> Self {
+        let base = (
+            config.size.0 as f32 - config.origin.0 as f32,
+            config.size.1 as f32 - config.origin.1 as f32,
+        );
+        let zoom = if config.zoom > 0.0 { config.zoom } else { 1.0 };
+        let (size, pixel_snap) = match (screen, config.scaling) {
+            (None, _) | (_, ScalingMode::Stretch) => ((base.0 / zoom, base.1 / zoom), None),
+            (Some((width, height)), ScalingMode::Fit) => {
+                let scale = (width / base.0).min(height / base.1) * zoom;
+                ((width / scale, height / scale), None)
+            }
+            (Some((width, height)), ScalingMode::PixelPerfect) => {
+                // Any fractional scale would draw some texels wider than others
+                let scale = ((width / base.0).min(height / base.1) * zoom).floor().max(1.0);
+                ((width / scale, height / scale), Some(1.0 / scale))
+            }
+        };
+        Self {
+            center_offset: config.center_offset(),
+            half_size: (size.0 / 2.0, size.1 / 2.0),
+            pixel_snap,
+        }
+    }
+
+    /// The world area the camera sees when it's at `position`
+    pub fn view_bounds(&self, position: (f32, f32)) -> Rect {
+        Rect {
+            left: self.center_offset.0 - self.half_size.0,
+            right: self.center_offset.0 + self.half_size.0,
+            bottom: self.center_offset.1 - self.half_size.1,
+            top: self.center_offset.1 + self.half_size.1,
+        }
+        .translate(position.0, position.1)
+    }
+
+    /// A camera showing this view
+    pub fn camera(&self, config: &CameraConfig) -> Camera {
+        Camera::from(Projection::orthographic(
+            self.center_offset.0 - self.half_size.0,
+            self.center_offset.0 + self.half_size.0,
+            self.center_offset.1 - self.half_size.1,
+            self.center_offset.1 + self.half_size.1,
+            config.znear,
+            config.zfar,
+        ))
+    }
+
+    /// Round a camera coordinate to the nearest whole screen pixel, when pixel snapping
+    pub fn snap(&self, value: f32) -> f32 {
+        match self.pixel_snap {
+            Some(pixel) => (value / pixel).round() * pixel,
+            None => value,
+        }
+    }
+}
+
+/// World position of the top left corner of every map. Tiles run rightwards and downwards from
+/// here, so the top edge of the map sits at y = 1.
+pub const MAP_ORIGIN: (f32, f32) = (0.0, 1.0);
+
+/// The area covered by the current map in world coordinates, which the camera is kept inside of.
+/// `None` when no map is loaded.
+#[derive(Default)]
+pub struct MapBounds(pub Option<Rect>);
+
+impl MapBounds {
+    pub fn from_map(map: &Map) -> Self {
+        MapBounds(Some(Rect {
+            left: MAP_ORIGIN.0,
+            right: MAP_ORIGIN.0 + (map.width * map.tile_width) as f32,
+            bottom: MAP_ORIGIN.1 - (map.height * map.tile_height) as f32,
+            top: MAP_ORIGIN.1,
+        }))
+    }
+}
+
+/// How an entity moves around the map
+///
+/// * `Free` - move smoothly in any direction with `Movement`
+/// * `Grid` - move exactly one tile per step with `GridMovement`
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub enum MovementKind {
+    Free,
+    Grid,
+}
+
+impl Default for MovementKind {
+    fn default() -> Self {
+        MovementKind::Free
+    }
+}
+
+/// Speed parameters for free movement. All values are in pixels per second, or pixels per
+/// second squared for the rates.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct SpeedConfig {
+    pub max_speed: f32,
+    pub acceleration: f32,
+    pub deceleration: f32,
+    /// Free movement's `max_speed` is multiplied by this while running
+    pub run_multiplier: f32,
+    /// Milliseconds a grid step takes
+    pub step_duration: u64,
+    /// Milliseconds a grid step takes while running
+    pub run_step_duration: u64,
+}
+
+impl Default for SpeedConfig {
+    fn default() -> Self {
+        Self {
+            max_speed: 128.,
+            acceleration: 1024.,
+            deceleration: 1024.,
+            run_multiplier: 1.75,
+            step_duration: 200,
+            run_step_duration: 120,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct PlayerConfig {
+    pub movement: MovementKind,
+    /// Where free movement comes to rest once the direction is released
+    #[serde(default)]
+    pub snap_mode: SnapMode,
+    #[serde(default)]
+    pub speed: SpeedConfig,
+    #[serde(default)]
+    pub input: InputConfig,
+}
+
+impl Asset for PlayerConfig {
+    const NAME: &'static str = "j::PlayerConfig";
+    type Data = Self;
+    type HandleStorage = VecStorage<Handle<PlayerConfig>>;
+}
+
+impl From<PlayerConfig> for Result<ProcessingState<PlayerConfig>, Error> {
+    fn from(player_config: PlayerConfig)
+        -> Result<ProcessingState<PlayerConfig>, Error> {
+            Ok(ProcessingState::Loaded(player_config))
+        }
+}
+
+/// Where a warp takes the player
+#[derive(Clone, Debug, PartialEq)]
+pub struct WarpTarget {
+    /// Path of the map to load, relative to the map the warp is on
+    pub map: String,
+    /// Name of the `player_spawn` object to place the player at
+    pub spawn: String,
+}
+
+/// Set by the warp system when the player steps onto a warp. The running state picks it up and
+/// switches maps.
+#[derive(Default)]
+pub struct PendingWarp(pub Option<WarpTarget>);
+
+/// Frames of a character's sprite sheet to play for every facing direction. Directions that
+/// aren't listed fall back to their cardinal direction.
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct CharacterAnimationConfig {
+    /// Milliseconds each frame is shown for
+    pub frame_duration: u64,
+    /// Frames shown while standing still
+    pub idle: HashMap<Direction, Vec<usize>>,
+    /// Frames shown while moving
+    pub walk: HashMap<Direction, Vec<usize>>,
+}
+
+impl Asset for CharacterAnimationConfig {
+    const NAME: &'static str = "j::CharacterAnimationConfig";
+    type Data = Self;
+    type HandleStorage = VecStorage<Handle<CharacterAnimationConfig>>;
+}
+
+impl From<CharacterAnimationConfig> for Result<ProcessingState<CharacterAnimationConfig>, Error> {
+    fn from(animation_config: CharacterAnimationConfig)
+        -> Result<ProcessingState<CharacterAnimationConfig>, Error> {
+            Ok(ProcessingState::Loaded(animation_config))
+        }
+}
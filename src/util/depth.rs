@@ -0,0 +1,40 @@
+//! Where things are drawn along z. The camera looks down from z = 1, so higher is closer.
+//!
+//! From back to front: tile layers at -10 and below, then sprites sorted by how far down the
+//! map their feet are, then layers marked `above` like tree tops and roofs.
+use crate::util::chunk::Rect;
+
+/// Sortable sprites at the very top of the map are drawn here
+pub const SORT_Z_FAR: f32 = -9.0;
+/// Sortable sprites at the very bottom of the map are drawn here, in front of everything above
+pub const SORT_Z_NEAR: f32 = -1.0;
+/// Layers marked `above` are drawn just below this, over every sortable sprite
+pub const ABOVE_Z: f32 = 0.5;
+
+/// The z of a sortable sprite whose feet are at `foot_y`, so that anything lower down the map is
+/// drawn in front of it
+///
+/// * `bounds` - area covered by the map
+pub fn sorted_z(foot_y: f32, bounds: &Rect) -> f32 {
+    let height = bounds.top - bounds.bottom;
+    if height <= 0.0 {
+        return SORT_Z_NEAR;
+    }
+    // 0 at the top of the map and 1 at the bottom. Sprites hanging off the edges are kept in the
+    // band so they can't end up behind the ground or over the roofs.
+    let depth = ((bounds.top - foot_y) / height).max(0.0).min(1.0);
+    SORT_Z_FAR + depth * (SORT_Z_NEAR - SORT_Z_FAR)
+}
+
+/// The z of a tile layer
+///
+/// * `index` - how far back the layer is, counting from 0 for the topmost layer in Tiled
+/// * `above` - whether the layer is drawn over sortable sprites
+pub fn layer_z(index: usize, above: bool) -> f32 {
+    if above {
+        // Small steps, so dozens of layers still fit between the sprites and the camera
+        ABOVE_Z - 0.01 * (index + 1) as f32
+    } else {
+        -10.0 * (index + 1) as f32
+    }
+}
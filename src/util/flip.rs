@@ -0,0 +1,190 @@
+//! Tiled stores whether a tile is flipped in the top bits of its global tile ID. These have to
+//! be masked out before the ID can be used to find a tile, and turned into a transform so the
+//! tile is drawn the way it was placed.
+use amethyst::core::{
+    math::{UnitQuaternion, Vector3},
+    Float, Transform,
+};
+
+pub const FLIPPED_HORIZONTALLY: u32 = 0x8000_0000;
+pub const FLIPPED_VERTICALLY: u32 = 0x4000_0000;
+/// Flipped across the diagonal from the top left to the bottom right, which Tiled combines with
+/// the other flips to rotate tiles by 90 degrees
+pub const FLIPPED_DIAGONALLY: u32 = 0x2000_0000;
+const FLIP_FLAGS: u32 = FLIPPED_HORIZONTALLY | FLIPPED_VERTICALLY | FLIPPED_DIAGONALLY;
+
+/// How a placed tile is flipped
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct TileFlip {
+    pub horizontal: bool,
+    pub vertical: bool,
+    pub diagonal: bool,
+}
+
+/// Split a global tile ID from a layer or tile object into the plain ID and how it's flipped
+pub fn decode_gid(gid: u32) -> (u32, TileFlip) {
+    (
+        gid & !FLIP_FLAGS,
+        TileFlip {
+            horizontal: gid & FLIPPED_HORIZONTALLY != 0,
+            vertical: gid & FLIPPED_VERTICALLY != 0,
+            diagonal: gid & FLIPPED_DIAGONALLY != 0,
+        },
+    )
+}
+
+impl TileFlip {
+    pub fn is_flipped(self) -> bool {
+        self.horizontal || self.vertical || self.diagonal
+    }
+
+    /// The rotation around z in radians, and the x and y scale, that draw a sprite flipped like
+    /// this. A flip is a mirror image, which we can only get from a negative scale, while two
+    /// flips together are a rotation.
+    pub fn rotation_and_scale(self) -> (f32, (f32, f32)) {
+        // Tiled flips diagonally first, then horizontally, then vertically. Our y axis points up
+        // where Tiled's points down, which makes the diagonal flip negate as well as swap.
+        let mut matrix = [[1, 0], [0, 1]];
+        if self.diagonal {
+            matrix = multiply([[0, -1], [-1, 0]], matrix);
+        }
+        if self.horizontal {
+            matrix = multiply([[-1, 0], [0, 1]], matrix);
+        }
+        if self.vertical {
+            matrix = multiply([[1, 0], [0, -1]], matrix);
+        }
+
+        let determinant = matrix[0][0] * matrix[1][1] - matrix[0][1] * matrix[1][0];
+        if determinant > 0 {
+            // No mirroring left over, so it's a plain rotation
+            ((matrix[1][0] as f32).atan2(matrix[0][0] as f32), (1.0, 1.0))
+        } else {
+            // Mirror x first, and rotate whatever is left
+            ((-matrix[1][0] as f32).atan2(-matrix[0][0] as f32), (-1.0, 1.0))
+        }
+    }
+
+    /// Which pixel of the original tile ends up at `pixel` once the tile is flipped like this.
+    /// Both are measured from the top left, like Tiled does.
+    ///
+    /// * `size` - width and height of the tile. Diagonal flips are only exact for square tiles.
+    pub fn source_pixel(self, pixel: (u32, u32), size: (u32, u32)) -> (u32, u32) {
+        // Undo the flips in the opposite order Tiled applies them
+        let (mut x, mut y) = pixel;
+        if self.vertical {
+            y = size.1 - 1 - y;
+        }
+        if self.horizontal {
+            x = size.0 - 1 - x;
+        }
+        if self.diagonal {
+            std::mem::swap(&mut x, &mut y);
+        }
+        (x, y)
+    }
+
+    /// Rotate and scale `transform` to draw its sprite flipped like this
+    pub fn apply(self, transform: &mut Transform) {
+        if !self.is_flipped() {
+            return;
+        }
+        let (rotation, (scale_x, scale_y)) = self.rotation_and_scale();
+        transform.set_rotation(UnitQuaternion::from_axis_angle(
+            &Vector3::z_axis(),
+            Float::from(rotation),
+        ));
+        transform.set_scale(Vector3::new(
+            Float::from(scale_x),
+            Float::from(scale_y),
+            Float::from(1.0),
+        ));
+    }
+}
+
+fn multiply(a: [[i32; 2]; 2], b: [[i32; 2]; 2]) -> [[i32; 2]; 2] {
+    let mut product = [[0; 2]; 2];
+    for row in 0..2 {
+        for column in 0..2 {
+            product[row][column] = a[row][0] * b[0][column] + a[row][1] * b[1][column];
+        }
+    }
+    product
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f32::consts::{FRAC_PI_2, PI};
+
+    fn flip(horizontal: bool, vertical: bool, diagonal: bool) -> TileFlip {
+        TileFlip {
+            horizontal,
+            vertical,
+            diagonal,
+        }
+    }
+
+    /// Middle of a pixel of a 4x4 tile, relative to the middle of the tile with y pointing up,
+    /// which is how the sprite is placed in the world
+    fn world_offset((x, y): (u32, u32)) -> (f32, f32) {
+        (x as f32 + 0.5 - 2.0, 2.0 - (y as f32 + 0.5))
+    }
+
+    #[test]
+    fn every_combination_of_flips() {
+        // H, V, D, then the rotation and scale, then where the top left pixel and the one right of
+        // it are taken from
+        let table = [
+            ((false, false, false), 0.0, (1.0, 1.0), (0, 0), (1, 0)),
+            ((true, false, false), 0.0, (-1.0, 1.0), (3, 0), (2, 0)),
+            ((false, true, false), PI, (-1.0, 1.0), (0, 3), (1, 3)),
+            ((true, true, false), PI, (1.0, 1.0), (3, 3), (2, 3)),
+            ((false, false, true), FRAC_PI_2, (-1.0, 1.0), (0, 0), (0, 1)),
+            // Rotated 90 degrees clockwise
+            ((true, false, true), -FRAC_PI_2, (1.0, 1.0), (0, 3), (0, 2)),
+            // Rotated 90 degrees anticlockwise
+            ((false, true, true), FRAC_PI_2, (1.0, 1.0), (3, 0), (3, 1)),
+            ((true, true, true), -FRAC_PI_2, (-1.0, 1.0), (3, 3), (3, 2)),
+        ];
+        for &((horizontal, vertical, diagonal), rotation, scale, top_left, next) in table.iter() {
+            let flip = flip(horizontal, vertical, diagonal);
+            let (actual_rotation, actual_scale) = flip.rotation_and_scale();
+            assert!(
+                (actual_rotation - rotation).abs() < 1e-6,
+                "{:?} rotates by {}",
+                flip,
+                actual_rotation
+            );
+            assert_eq!(actual_scale, scale, "{:?}", flip);
+            assert_eq!(flip.source_pixel((0, 0), (4, 4)), top_left, "{:?}", flip);
+            assert_eq!(flip.source_pixel((1, 0), (4, 4)), next, "{:?}", flip);
+
+            // Drawing the sprite with that rotation and scale puts every source pixel where the
+            // flip says it should end up
+            let (sin, cos) = actual_rotation.sin_cos();
+            for y in 0..4 {
+                for x in 0..4 {
+                    let (source_x, source_y) = world_offset(flip.source_pixel((x, y), (4, 4)));
+                    let (scaled_x, scaled_y) = (source_x * actual_scale.0, source_y * actual_scale.1);
+                    let drawn = (cos * scaled_x - sin * scaled_y, sin * scaled_x + cos * scaled_y);
+                    let expected = world_offset((x, y));
+                    assert!(
+                        (drawn.0 - expected.0).abs() < 1e-5 && (drawn.1 - expected.1).abs() < 1e-5,
+                        "{:?} draws pixel {:?} at {:?}",
+                        flip,
+                        (x, y),
+                        drawn
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn flags_are_masked_out_of_the_id() {
+        let gid = 7 | FLIPPED_HORIZONTALLY | FLIPPED_DIAGONALLY;
+        assert_eq!(decode_gid(gid), (7, flip(true, false, true)));
+        assert_eq!(decode_gid(7), (7, TileFlip::default()));
+    }
+}
@@ -0,0 +1,211 @@
+//! Keeps track of which actions are held, so movement can be worked out from the whole keyboard
+//! state instead of from whichever event happened to come last.
+use crate::util::data::Action;
+
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+/// What to do when both directions of an axis are held, like Left and Right together
+///
+/// * `LastPressed` - the most recently pressed direction wins
+/// * `Cancel` - the two cancel out and we don't move along that axis
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub enum OpposingKeys {
+    LastPressed,
+    Cancel,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct InputConfig {
+    pub opposing_keys: OpposingKeys,
+    /// How long a directional tap is remembered for in milliseconds, so a tap made while a grid
+    /// step is still underway starts the next step instead of being dropped
+    pub buffer: u64,
+}
+
+impl Default for InputConfig {
+    fn default() -> Self {
+        Self {
+            opposing_keys: OpposingKeys::LastPressed,
+            buffer: 250,
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct InputState {
+    /// Held actions, in the order they were pressed
+    held: Vec<Action>,
+    /// The last directional press, and when it happened
+    buffered: Option<(Action, Duration)>,
+}
+
+impl InputState {
+    /// Record a press. Pressing something already held changes nothing.
+    ///
+    /// * `now` - time of the press, used to expire the buffer
+    pub fn press(&mut self, action: Action, now: Duration) {
+        if self.is_held(&action) {
+            return;
+        }
+        if action_direction(&action).is_some() {
+            self.buffered = Some((action.clone(), now));
+        }
+        self.held.push(action);
+    }
+
+    /// Record a release. Releasing something that isn't held is ignored, so a stray release
+    /// can't cancel out another key.
+    pub fn release(&mut self, action: &Action) {
+        self.held.retain(|held| held != action);
+    }
+
+    pub fn is_held(&self, action: &Action) -> bool {
+        self.held.contains(action)
+    }
+
+    /// The held direction along each axis, as -1, 0 or 1 with y pointing up
+    pub fn direction(&self, opposing_keys: OpposingKeys) -> (f32, f32) {
+        (
+            self.resolve_axis(true, opposing_keys),
+            self.resolve_axis(false, opposing_keys),
+        )
+    }
+
+    /// The single most recently pressed direction that's still held and not cancelled out. Grid
+    /// movement can't go diagonally, so this is what it follows.
+    pub fn last_direction(&self, opposing_keys: OpposingKeys) -> Option<Action> {
+        self.held
+            .iter()
+            .rev()
+            .find(|action| match action_direction(action) {
+                Some((x, y)) if y == 0.0 => self.resolve_axis(true, opposing_keys) == x,
+                Some((x, y)) if x == 0.0 => self.resolve_axis(false, opposing_keys) == y,
+                _ => false,
+            })
+            .cloned()
+    }
+
+    /// Take the buffered directional tap, if it happened within `buffer` of `now`
+    pub fn take_buffered(&mut self, now: Duration, buffer: Duration) -> Option<Action> {
+        match self.buffered.take() {
+            Some((action, pressed)) if now - pressed <= buffer => Some(action),
+            _ => None,
+        }
+    }
+
+    fn resolve_axis(&self, horizontal: bool, opposing_keys: OpposingKeys) -> f32 {
+        let mut pressed = self
+            .held
+            .iter()
+            .filter_map(action_direction)
+            .map(|(x, y)| if horizontal { x } else { y })
+            .filter(|value| *value != 0.0);
+        let first = match pressed.next() {
+            Some(value) => value,
+            None => return 0.0,
+        };
+        match (pressed.last(), opposing_keys) {
+            (None, _) => first,
+            (Some(last), OpposingKeys::LastPressed) => last,
+            (Some(_), OpposingKeys::Cancel) => 0.0,
+        }
+    }
+}
+
+/// The direction a directional action moves us in, with y pointing up. Other actions have none.
+pub fn action_direction(action: &Action) -> Option<(f32, f32)> {
+    match action {
+        Action::Left => Some((-1.0, 0.0)),
+        Action::Right => Some((1.0, 0.0)),
+        Action::Up => Some((0.0, 1.0)),
+        Action::Down => Some((0.0, -1.0)),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ms(millis: u64) -> Duration {
+        Duration::from_millis(millis)
+    }
+
+    #[test]
+    fn last_pressed_follows_the_newest_key_and_falls_back_on_release() {
+        let mut input = InputState::default();
+        input.press(Action::Left, ms(0));
+        assert_eq!(input.direction(OpposingKeys::LastPressed), (-1.0, 0.0));
+        input.press(Action::Right, ms(10));
+        assert_eq!(input.direction(OpposingKeys::LastPressed), (1.0, 0.0));
+        assert_eq!(input.last_direction(OpposingKeys::LastPressed), Some(Action::Right));
+
+        // Left is still held, so we go back to it
+        input.release(&Action::Right);
+        assert_eq!(input.direction(OpposingKeys::LastPressed), (-1.0, 0.0));
+        assert_eq!(input.last_direction(OpposingKeys::LastPressed), Some(Action::Left));
+
+        // A release of something that isn't held doesn't touch Left
+        input.release(&Action::Right);
+        assert_eq!(input.direction(OpposingKeys::LastPressed), (-1.0, 0.0));
+        assert!(input.is_held(&Action::Left));
+    }
+
+    #[test]
+    fn cancel_stops_the_axis_while_both_are_held() {
+        let mut input = InputState::default();
+        input.press(Action::Left, ms(0));
+        input.press(Action::Right, ms(10));
+        input.press(Action::Up, ms(20));
+        assert_eq!(input.direction(OpposingKeys::Cancel), (0.0, 1.0));
+        assert_eq!(input.last_direction(OpposingKeys::Cancel), Some(Action::Up));
+
+        input.release(&Action::Up);
+        assert_eq!(input.direction(OpposingKeys::Cancel), (0.0, 0.0));
+        assert_eq!(input.last_direction(OpposingKeys::Cancel), None);
+
+        input.release(&Action::Left);
+        assert_eq!(input.direction(OpposingKeys::Cancel), (1.0, 0.0));
+    }
+
+    #[test]
+    fn pressing_a_held_key_again_changes_nothing() {
+        let mut input = InputState::default();
+        input.press(Action::Left, ms(0));
+        input.press(Action::Right, ms(10));
+        // Key repeat sends Left again, which mustn't make it the newest press
+        input.press(Action::Left, ms(20));
+        assert_eq!(input.direction(OpposingKeys::LastPressed), (1.0, 0.0));
+    }
+
+    #[test]
+    fn taps_during_a_step_are_buffered() {
+        let mut input = InputState::default();
+        // Tapped and let go before the step underway finished
+        input.press(Action::Up, ms(100));
+        input.release(&Action::Up);
+        assert_eq!(input.direction(OpposingKeys::LastPressed), (0.0, 0.0));
+        assert_eq!(input.take_buffered(ms(300), ms(250)), Some(Action::Up));
+        // Taken once, so the next step doesn't repeat it
+        assert_eq!(input.take_buffered(ms(300), ms(250)), None);
+    }
+
+    #[test]
+    fn buffered_taps_expire() {
+        let mut input = InputState::default();
+        input.press(Action::Up, ms(100));
+        input.release(&Action::Up);
+        assert_eq!(input.take_buffered(ms(351), ms(250)), None);
+    }
+
+    #[test]
+    fn only_directions_are_buffered() {
+        let mut input = InputState::default();
+        input.press(Action::Left, ms(0));
+        input.press(Action::Interact, ms(10));
+        assert!(input.is_held(&Action::Interact));
+        assert_eq!(input.direction(OpposingKeys::LastPressed), (-1.0, 0.0));
+        assert_eq!(input.take_buffered(ms(20), ms(250)), Some(Action::Left));
+    }
+}
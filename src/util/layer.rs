@@ -0,0 +1,68 @@
+//! How a tile layer is drawn, from the layer's settings in Tiled and its custom properties
+use crate::util::depth::layer_z;
+
+use tiled::{Layer, PropertyValue};
+
+/// Property overriding how far back a layer is drawn. Higher is closer to the camera.
+pub const Z_PROPERTY: &str = "z";
+/// Boolean property drawing a layer over characters, for things like tree tops and roofs
+pub const ABOVE_PROPERTY: &str = "above";
+/// Properties setting how fast a layer scrolls compared to the map, where 1 scrolls along with
+/// it, 0 stays still on screen, and anything above 1 scrolls faster like a foreground
+pub const PARALLAX_X_PROPERTY: &str = "parallax_x";
+pub const PARALLAX_Y_PROPERTY: &str = "parallax_y";
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct LayerSettings {
+    pub visible: bool,
+    /// From 0 for invisible to 1 for opaque
+    pub opacity: f32,
+    /// How far the whole layer is shifted in world units, with y pointing up
+    pub offset: (f32, f32),
+    /// Depth of the layer, if its properties set one
+    pub z: Option<f32>,
+    /// Whether the layer is drawn over depth sorted sprites
+    pub above: bool,
+    pub parallax: (f32, f32),
+}
+
+impl LayerSettings {
+    pub fn from_layer(layer: &Layer) -> Self {
+        Self {
+            visible: layer.visible,
+            opacity: layer.opacity,
+            // Tiled measures offsets with y pointing down
+            offset: (layer.offset_x, -layer.offset_y),
+            z: float_property(layer, Z_PROPERTY),
+            above: match layer.properties.get(ABOVE_PROPERTY) {
+                Some(PropertyValue::BoolValue(above)) => *above,
+                _ => false,
+            },
+            parallax: (
+                float_property(layer, PARALLAX_X_PROPERTY).unwrap_or(1.0),
+                float_property(layer, PARALLAX_Y_PROPERTY).unwrap_or(1.0),
+            ),
+        }
+    }
+
+    /// The z to draw the layer at, unless its `z` property says otherwise
+    ///
+    /// * `index` - how far back the layer is, counting from 0 for the topmost layer in Tiled
+    pub fn depth(&self, index: usize) -> f32 {
+        self.z.unwrap_or_else(|| layer_z(index, self.above))
+    }
+
+    /// Whether the layer scrolls any differently from the map
+    pub fn has_parallax(&self) -> bool {
+        self.parallax != (1.0, 1.0)
+    }
+}
+
+/// A number property of a layer, whether it was set as a float or an int
+fn float_property(layer: &Layer, name: &str) -> Option<f32> {
+    match layer.properties.get(name) {
+        Some(PropertyValue::FloatValue(value)) => Some(*value),
+        Some(PropertyValue::IntValue(value)) => Some(*value as f32),
+        _ => None,
+    }
+}
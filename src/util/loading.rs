@@ -0,0 +1,112 @@
+//! Works out whether the assets tracked by progress counters have loaded, failed or are still on
+//! their way
+use amethyst::assets::ProgressCounter;
+
+use std::{fmt, time::Duration};
+
+/// How long a load state waits on its assets before giving up
+pub const LOAD_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// An asset that couldn't be loaded, and why
+#[derive(Clone, Debug)]
+pub struct LoadError {
+    /// Name of the asset, which is usually its path
+    pub asset: String,
+    pub reason: String,
+}
+
+impl LoadError {
+    pub fn new(asset: &str, reason: String) -> Self {
+        Self {
+            asset: asset.to_string(),
+            reason,
+        }
+    }
+
+    /// `asset` was still loading after `timeout`
+    pub fn timed_out(asset: &str, timeout: Duration) -> Self {
+        Self::new(
+            asset,
+            format!("still loading after {} seconds", timeout.as_secs()),
+        )
+    }
+}
+
+impl fmt::Display for LoadError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "`{}`: {}", self.asset, self.reason)
+    }
+}
+
+#[derive(Debug)]
+pub enum LoadStatus {
+    Loading,
+    Loaded,
+    Failed(Vec<LoadError>),
+}
+
+/// The status of everything tracked by `counters` together. Any failure fails the lot, even if
+/// other assets are still loading. A failed asset never counts as finished, so waiting on
+/// `ProgressCounter::is_complete` alone would wait forever.
+pub fn load_status(counters: &[&ProgressCounter]) -> LoadStatus {
+    let errors: Vec<LoadError> = counters
+        .iter()
+        .flat_map(|counter| counter.errors())
+        .map(|error| LoadError::new(&error.asset_name, error.error.to_string()))
+        .collect();
+    if !errors.is_empty() {
+        LoadStatus::Failed(errors)
+    } else if counters.iter().all(|counter| counter.is_complete()) {
+        LoadStatus::Loaded
+    } else {
+        LoadStatus::Loading
+    }
+}
+
+/// How many of the assets being loaded are done, for anything that wants to show it
+#[derive(Clone, Debug, Default)]
+pub struct LoadProgress {
+    pub loaded: usize,
+    pub total: usize,
+}
+
+/// Every error that sent us to the `ErrorState`. It's added as a resource when that state
+/// starts, for anything that wants to show or check what went wrong.
+#[derive(Clone, Debug, Default)]
+pub struct LoadFailure {
+    pub errors: Vec<LoadError>,
+}
+
+/// Keeps track of when loading started, so a load that never finishes can be given up on
+pub struct LoadDeadline {
+    started: Option<Duration>,
+    timeout: Duration,
+}
+
+impl Default for LoadDeadline {
+    fn default() -> Self {
+        Self::new(LOAD_TIMEOUT)
+    }
+}
+
+impl LoadDeadline {
+    pub fn new(timeout: Duration) -> Self {
+        Self {
+            started: None,
+            timeout,
+        }
+    }
+
+    /// How long loading may take before we give up
+    pub fn timeout(&self) -> Duration {
+        self.timeout
+    }
+
+    /// Whether `timeout` has passed since the first time this was called
+    ///
+    /// * `now` - the current real time, such as `Time::absolute_real_time`
+    pub fn expired(&mut self, now: Duration) -> bool {
+        let started = *self.started.get_or_insert(now);
+        now - started > self.timeout
+    }
+}
@@ -0,0 +1,196 @@
+//! A RON manifest listing the assets to load before the game starts, and the typed registry of
+//! handles it gets loaded into. How each kind of asset is loaded is registered in `AssetLoaders`,
+//! so new kinds can be added without touching the manifest.
+use crate::util::loading::LoadError;
+
+use amethyst::{
+    assets::{Asset, AssetStorage, Format, Handle, Loader, ProcessingState, ProgressCounter, RonFormat},
+    ecs::{VecStorage, World},
+    error::Error,
+    renderer::{
+        formats::texture::ImageFormat,
+        sprite::{SpriteSheet, SpriteSheetFormat},
+        Texture,
+    },
+};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::{any::Any, collections::HashMap};
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct AssetEntry {
+    /// Asset path of the file
+    pub path: String,
+    /// Which registered loader reads the file, like `Texture` or `SpriteSheet`
+    pub kind: String,
+    /// Anything else the loader needs, like the image a sprite sheet is cut from
+    #[serde(default)]
+    pub options: HashMap<String, String>,
+}
+
+impl AssetEntry {
+    /// The option named `name`, or an error saying the loader needs it
+    pub fn option(&self, name: &str) -> Result<&str, String> {
+        self.options
+            .get(name)
+            .map(String::as_str)
+            .ok_or_else(|| format!("a {} needs the `{}` option", self.kind, name))
+    }
+}
+
+/// Every asset to load, by the id the game looks it up with
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct AssetManifest {
+    pub assets: HashMap<String, AssetEntry>,
+}
+
+impl AssetManifest {
+    /// Start loading everything in the manifest through the loaders registered for each kind,
+    /// tracking textures with `textures` and everything else with `progress`. Entries that can't
+    /// be loaded, like ones of a kind nobody registered, are added to `errors`.
+    pub fn load(
+        &self,
+        world: &World,
+        loaders: &AssetLoaders,
+        progress: &mut ProgressCounter,
+        textures: &mut ProgressCounter,
+        errors: &mut Vec<LoadError>,
+    ) -> AssetHandles {
+        let mut handles = AssetHandles::default();
+        for (id, entry) in self.assets.iter() {
+            let loaded = match loaders.loaders.get(&entry.kind) {
+                Some(load) => load(world, entry, progress, textures),
+                None => Err(format!("no loader is registered for the kind `{}`", entry.kind)),
+            };
+            match loaded {
+                Ok(handle) => {
+                    handles.handles.insert(id.to_string(), handle);
+                }
+                Err(reason) => errors.push(LoadError::new(id, reason)),
+            }
+        }
+        handles
+    }
+}
+
+/// Start loading the file of a manifest entry, returning a handle to it. Loaders count textures
+/// with the second progress counter and everything else with the first.
+pub type LoadFn = Box<
+    dyn Fn(&World, &AssetEntry, &mut ProgressCounter, &mut ProgressCounter)
+            -> Result<Box<dyn Any + Send + Sync>, String>
+        + Send
+        + Sync,
+>;
+
+/// How to load each kind of asset a manifest can list, by the name entries give as their `kind`.
+/// Textures and sprite sheets are known from the start, everything else is registered by the
+/// game.
+pub struct AssetLoaders {
+    loaders: HashMap<String, LoadFn>,
+}
+
+impl Default for AssetLoaders {
+    fn default() -> Self {
+        Self {
+            loaders: HashMap::new(),
+        }
+        .with::<Texture, _>("Texture", |world, entry, _, textures| {
+            Ok(load(world, &entry.path, ImageFormat::default(), textures))
+        })
+        .with::<SpriteSheet, _>("SpriteSheet", |world, entry, progress, textures| {
+            let texture = load(world, entry.option("texture")?, ImageFormat::default(), textures);
+            Ok(load(world, &entry.path, SpriteSheetFormat(texture), progress))
+        })
+    }
+}
+
+impl AssetLoaders {
+    /// Register how to load a kind of asset, replacing any existing loader for it. The handle
+    /// it returns is stored as a `Handle<A>`, which is how it's looked up again.
+    pub fn with<A, F>(mut self, kind: &str, load: F) -> Self
+    where
+        A: Asset,
+        F: Fn(&World, &AssetEntry, &mut ProgressCounter, &mut ProgressCounter) -> Result<Handle<A>, String>
+            + Send
+            + Sync
+            + 'static,
+    {
+        self.register(kind, load);
+        self
+    }
+
+    pub fn register<A, F>(&mut self, kind: &str, load: F)
+    where
+        A: Asset,
+        F: Fn(&World, &AssetEntry, &mut ProgressCounter, &mut ProgressCounter) -> Result<Handle<A>, String>
+            + Send
+            + Sync
+            + 'static,
+    {
+        self.loaders.insert(
+            kind.to_string(),
+            Box::new(move |world, entry, progress, textures| {
+                let handle = load(world, entry, progress, textures)?;
+                Ok(Box::new(handle) as Box<dyn Any + Send + Sync>)
+            }),
+        );
+    }
+
+    /// Register a kind of asset read straight from a RON file, like our configs
+    pub fn with_ron<A>(self, kind: &str) -> Self
+    where
+        A: Asset,
+        A::Data: DeserializeOwned,
+    {
+        self.with::<A, _>(kind, |world, entry, progress, _| {
+            Ok(load(world, &entry.path, RonFormat, progress))
+        })
+    }
+}
+
+impl Asset for AssetManifest {
+    const NAME: &'static str = "j::AssetManifest";
+    type Data = Self;
+    type HandleStorage = VecStorage<Handle<AssetManifest>>;
+}
+
+impl From<AssetManifest> for Result<ProcessingState<AssetManifest>, Error> {
+    fn from(manifest: AssetManifest)
+        -> Result<ProcessingState<AssetManifest>, Error> {
+            Ok(ProcessingState::Loaded(manifest))
+        }
+}
+
+/// Handles of every asset loaded from a manifest, by id. Handles of any asset type live side by
+/// side, and come back out with the type they were put in with. Once loaded they're kept as a
+/// resource, so later maps reuse them instead of reading the manifest again.
+#[derive(Default)]
+pub struct AssetHandles {
+    handles: HashMap<String, Box<dyn Any + Send + Sync>>,
+}
+
+impl AssetHandles {
+    pub fn insert<A: Asset>(&mut self, id: &str, handle: Handle<A>) {
+        self.handles.insert(id.to_string(), Box::new(handle));
+    }
+
+    /// The handle stored under `id`, if there is one and it's a handle to an `A`
+    pub fn get<A: Asset>(&self, id: &str) -> Option<Handle<A>> {
+        self.handles
+            .get(id)?
+            .downcast_ref::<Handle<A>>()
+            .cloned()
+    }
+}
+
+fn load<A, F>(world: &World, path: &str, format: F, progress: &mut ProgressCounter) -> Handle<A>
+where
+    A: Asset,
+    F: Format<A::Data>,
+{
+    world.read_resource::<Loader>().load(
+        path,
+        format,
+        progress,
+        &world.read_resource::<AssetStorage<A>>(),
+    )
+}
@@ -1,4 +1,18 @@
+pub mod bindings;
+pub mod chunk;
+pub mod collision;
 mod common;
 pub mod data;
+pub mod depth;
+pub mod flip;
+pub mod input;
+pub mod layer;
+pub mod loading;
+pub mod manifest;
+pub mod objects;
+pub mod path;
+pub mod tileset;
+pub mod tmx;
+pub mod watch;
 
 pub use common::*;
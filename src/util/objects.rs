@@ -0,0 +1,169 @@
+//! Turns objects from Tiled object layers into entities. Every object type has a spawn function,
+//! looked up by the object's `type` in a `SpawnRegistry` resource, so new kinds of objects can be
+//! added without touching the map builder.
+use crate::{
+    components::{DepthSort, Npc, Trigger, Warp},
+    util::data::{WarpTarget, MAP_ORIGIN},
+};
+
+use amethyst::{
+    core::{math::Vector3, Float, Transform},
+    ecs::prelude::*,
+    renderer::SpriteRender,
+};
+use std::collections::HashMap;
+use tiled::{Map, Object, Properties, PropertyValue};
+
+/// Object type marking where the player starts on a map. It is handled by the map builder
+/// itself, so it never goes through the registry.
+pub const PLAYER_SPAWN: &str = "player_spawn";
+/// Property naming the map a warp leads to, on warp objects or on tiles in a tileset
+pub const WARP_MAP_PROPERTY: &str = "map";
+/// Property naming the `player_spawn` object a warp puts the player at
+pub const WARP_SPAWN_PROPERTY: &str = "spawn";
+
+/// Everything a spawn function needs to know about the object it's creating
+pub struct MapObject {
+    pub object: Object,
+    /// Transform at the center of the object
+    pub transform: Transform,
+    /// If the object was placed as a tile, this renders that tile
+    pub sprite: Option<SpriteRender>,
+}
+
+/// Create an entity for a map object, returning it
+pub type SpawnFn = fn(&mut World, &MapObject) -> Entity;
+
+/// Spawn functions by object type
+#[derive(Clone)]
+pub struct SpawnRegistry {
+    spawners: HashMap<String, SpawnFn>,
+}
+
+impl Default for SpawnRegistry {
+    fn default() -> Self {
+        Self {
+            spawners: HashMap::new(),
+        }
+        .with("npc", spawn_npc)
+        .with("trigger", spawn_trigger)
+        .with("door", spawn_warp)
+        .with("warp", spawn_warp)
+    }
+}
+
+impl SpawnRegistry {
+    /// Register a spawn function for an object type, replacing any existing one
+    pub fn with(mut self, object_type: &str, spawner: SpawnFn) -> Self {
+        self.register(object_type, spawner);
+        self
+    }
+
+    pub fn register(&mut self, object_type: &str, spawner: SpawnFn) {
+        self.spawners.insert(object_type.to_string(), spawner);
+    }
+
+    /// Spawn an object through the function registered for its type. Objects with a type we
+    /// don't know about are skipped.
+    pub fn spawn(&self, world: &mut World, object: &MapObject) -> Option<Entity> {
+        self.spawners
+            .get(&object.object.obj_type)
+            .map(|spawner| spawner(world, object))
+    }
+}
+
+/// World position of the center of an object.
+///
+/// Tiled measures objects in pixels from the top left of the map, with y pointing down. Plain
+/// objects are anchored at their top left corner, while tile objects are anchored at their
+/// bottom left.
+pub fn object_translation(object: &Object) -> Vector3<Float> {
+    let center_y = if object.gid != 0 {
+        object.y - object.height / 2.0
+    } else {
+        object.y + object.height / 2.0
+    };
+    Vector3::new(
+        Float::from(MAP_ORIGIN.0 + object.x + object.width / 2.0),
+        Float::from(MAP_ORIGIN.1 - center_y),
+        Float::from(0.0),
+    )
+}
+
+/// All objects of every object layer in the map
+pub fn objects(map: &Map) -> impl Iterator<Item = &Object> {
+    map.object_groups
+        .iter()
+        .flat_map(|group| group.objects.iter())
+}
+
+/// Where the player should start on this map, if the map says so. If `name` is given, the spawn
+/// point with that name is preferred, otherwise we fall back to the first one.
+pub fn player_spawn(map: &Map, name: Option<&str>) -> Option<Vector3<Float>> {
+    let mut spawns = objects(map).filter(|object| object.obj_type == PLAYER_SPAWN);
+    let named = name.and_then(|name| spawns.clone().find(|object| object.name == name));
+    named.or_else(|| spawns.next()).map(object_translation)
+}
+
+/// Read the warp target out of object or tile properties, if they name a map
+pub fn warp_target(properties: &Properties) -> Option<WarpTarget> {
+    let map = string_property(properties, WARP_MAP_PROPERTY)?;
+    Some(WarpTarget {
+        map,
+        spawn: string_property(properties, WARP_SPAWN_PROPERTY).unwrap_or_default(),
+    })
+}
+
+fn string_property(properties: &Properties, key: &str) -> Option<String> {
+    match properties.get(key) {
+        Some(PropertyValue::StringValue(value)) => Some(value.clone()),
+        _ => None,
+    }
+}
+
+fn spawn_npc(world: &mut World, object: &MapObject) -> Entity {
+    world.register::<Npc>();
+    world.register::<DepthSort>();
+    let mut builder = world
+        .create_entity()
+        .with(object.transform.clone())
+        .with(Npc {
+            name: object.object.name.clone(),
+        });
+    // NPCs drawn from a tile get sorted against the player, with their feet at the bottom of it
+    if let Some(sprite) = object.sprite.clone() {
+        builder = builder.with(sprite).with(DepthSort {
+            foot_offset: object.object.height / 2.0,
+        });
+    }
+    builder.build()
+}
+
+fn spawn_trigger(world: &mut World, object: &MapObject) -> Entity {
+    world.register::<Trigger>();
+    world
+        .create_entity()
+        .with(object.transform.clone())
+        .with(Trigger {
+            name: object.object.name.clone(),
+            size: (object.object.width, object.object.height),
+            properties: object.object.properties.clone(),
+        })
+        .build()
+}
+
+fn spawn_warp(world: &mut World, object: &MapObject) -> Entity {
+    world.register::<Warp>();
+    let mut builder = world.create_entity().with(object.transform.clone());
+    // A door without a target is just decoration
+    if let Some(target) = warp_target(&object.object.properties) {
+        builder = builder.with(Warp {
+            target,
+            size: (object.object.width, object.object.height),
+        });
+    }
+    if let Some(sprite) = object.sprite.clone() {
+        builder = builder.with(sprite);
+    }
+    builder.build()
+}
@@ -0,0 +1,149 @@
+//! Resolution of paths that assets use to reference each other, like a map pointing at its
+//! tileset images. Everything here works on asset paths, which are relative to the assets root
+//! and always use `/` as a separator.
+use std::{error, fmt};
+
+/// Why a referenced path couldn't be turned into an asset path
+#[derive(Debug, Clone, PartialEq)]
+pub enum PathError {
+    /// The referenced path is absolute, so it can't be loaded through the asset loader
+    Absolute { base: String, path: String },
+    /// Following the `..` segments of the referenced path takes us outside of the assets root
+    EscapesRoot { base: String, path: String },
+}
+
+impl fmt::Display for PathError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PathError::Absolute { base, path } => write!(
+                f,
+                "`{}` references the absolute path `{}`, but only paths relative to it are supported",
+                base, path
+            ),
+            PathError::EscapesRoot { base, path } => write!(
+                f,
+                "`{}` references `{}`, which is outside of the assets directory",
+                base, path
+            ),
+        }
+    }
+}
+
+impl error::Error for PathError {}
+
+/// Resolve `path` relative to the directory of the asset at `base`.
+///
+/// * `base` - asset path of the file doing the referencing, such as `maps/first.tmx`
+/// * `path` - the path as it's written inside that file, such as `../textures/overworld.png`
+///
+/// `.` and `..` segments are normalised away, and Windows style separators are accepted since
+/// Tiled writes whatever the designer's platform uses.
+pub fn resolve_relative(base: &str, path: &str) -> Result<String, PathError> {
+    let path_normalized = path.replace('\\', "/");
+    if path_normalized.starts_with('/') || path_normalized.contains(':') {
+        return Err(PathError::Absolute {
+            base: base.to_string(),
+            path: path.to_string(),
+        });
+    }
+
+    // Start from the directory containing `base`, which is everything but the file name
+    let mut segments: Vec<&str> = base.split('/').filter(|s| !s.is_empty()).collect();
+    segments.pop();
+
+    for segment in path_normalized.split('/') {
+        match segment {
+            "" | "." => (),
+            ".." => {
+                if segments.pop().is_none() {
+                    return Err(PathError::EscapesRoot {
+                        base: base.to_string(),
+                        path: path.to_string(),
+                    });
+                }
+            }
+            segment => segments.push(segment),
+        }
+    }
+
+    Ok(segments.join("/"))
+}
+
+/// The opposite of `resolve_relative`: the path to write inside the asset at `base` so that it
+/// resolves to the asset path `target`.
+///
+/// * `base` - asset path of the file doing the referencing, such as `maps/first.tmx`
+/// * `target` - asset path of the file being referenced, such as `textures/overworld.png`
+pub fn relative_path(base: &str, target: &str) -> String {
+    let mut from: Vec<&str> = base.split('/').filter(|s| !s.is_empty()).collect();
+    from.pop();
+    let to: Vec<&str> = target.split('/').filter(|s| !s.is_empty()).collect();
+
+    let shared = from
+        .iter()
+        .zip(to.iter())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let mut segments = vec![".."; from.len() - shared];
+    segments.extend_from_slice(&to[shared..]);
+    segments.join("/")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn paths_resolve_from_nested_map_folders() {
+        assert_eq!(
+            resolve_relative("maps/sub/a.tmx", "../../textures/x.png"),
+            Ok("textures/x.png".to_string())
+        );
+        assert_eq!(
+            resolve_relative("maps/sub/a.tmx", "tiles/./x.png"),
+            Ok("maps/sub/tiles/x.png".to_string())
+        );
+        assert_eq!(
+            resolve_relative("maps/sub/a.tmx", "..\\b.tmx"),
+            Ok("maps/b.tmx".to_string())
+        );
+    }
+
+    #[test]
+    fn paths_escaping_the_assets_root_are_refused() {
+        assert_eq!(
+            resolve_relative("maps/sub/a.tmx", "../../../x.png"),
+            Err(PathError::EscapesRoot {
+                base: "maps/sub/a.tmx".to_string(),
+                path: "../../../x.png".to_string(),
+            })
+        );
+        assert!(resolve_relative("a.tmx", "../x.png").is_err());
+    }
+
+    #[test]
+    fn absolute_paths_are_refused() {
+        assert_eq!(
+            resolve_relative("maps/a.tmx", "/textures/x.png"),
+            Err(PathError::Absolute {
+                base: "maps/a.tmx".to_string(),
+                path: "/textures/x.png".to_string(),
+            })
+        );
+        assert!(resolve_relative("maps/a.tmx", "C:\\textures\\x.png").is_err());
+    }
+
+    #[test]
+    fn relative_paths_resolve_back_to_their_target() {
+        let cases = [
+            ("maps/sub/a.tmx", "textures/x.png", "../../textures/x.png"),
+            ("maps/a.tmx", "maps/tiles/b.tsx", "tiles/b.tsx"),
+            ("maps/sub/a.tmx", "maps/sub/c.png", "c.png"),
+            ("a.tmx", "textures/x.png", "textures/x.png"),
+        ];
+        for (base, target, relative) in cases.iter() {
+            assert_eq!(relative_path(base, target), *relative);
+            assert_eq!(resolve_relative(base, &relative_path(base, target)), Ok(target.to_string()));
+        }
+    }
+}
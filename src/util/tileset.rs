@@ -0,0 +1,192 @@
+//! Slices tileset images into tile sprites the same way Tiled does
+use amethyst::renderer::sprite::TextureCoordinates;
+use tiled::Tileset;
+
+/// Where the tiles of a tileset are in its image. Tiles run left to right and then top to
+/// bottom, starting `margin` pixels in from the top left edges, with `spacing` pixels between
+/// neighbouring tiles.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TileData {
+    pub tile_size: (u32, u32),
+    /// Size of the whole tileset image
+    pub tileset_size: (u32, u32),
+    pub margin: u32,
+    pub spacing: u32,
+    /// Number of columns, if Tiled told us. Otherwise it's worked out from the image size.
+    pub columns: Option<u32>,
+    /// Number of tiles, if Tiled told us. The last row isn't always full.
+    pub tile_count: Option<u32>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TileGrid<T> {
+    pub columns: T,
+    pub rows: T,
+}
+
+impl TileData {
+    /// The layout of a tileset's first image. Tilesets made of separate images per tile have
+    /// nothing to slice, so they have no layout.
+    pub fn from_tileset(set: &Tileset) -> Option<Self> {
+        let image = set.images.first()?;
+        Some(Self {
+            tile_size: (set.tile_width, set.tile_height),
+            tileset_size: (image.width as u32, image.height as u32),
+            margin: set.margin,
+            spacing: set.spacing,
+            columns: set.columns,
+            tile_count: set.tilecount,
+        })
+    }
+
+    /// How many whole tiles fit across and down the image
+    pub fn get_tileset_sprite_grid(&self) -> TileGrid<u32> {
+        TileGrid {
+            columns: self
+                .columns
+                .unwrap_or_else(|| self.fit(self.tileset_size.0, self.tile_size.0)),
+            rows: self.fit(self.tileset_size.1, self.tile_size.1),
+        }
+    }
+
+    /// Number of tiles in the tileset, which are numbered from 0
+    pub fn get_tile_count(&self) -> u32 {
+        let grid = self.get_tileset_sprite_grid();
+        let whole_grid = grid.columns * grid.rows;
+        self.tile_count
+            .map_or(whole_grid, |tile_count| tile_count.min(whole_grid))
+    }
+
+    /// Pixel position of the top left corner of a tile by its local ID. IDs past the end of the
+    /// tileset have none.
+    pub fn get_tile_position(&self, id: u32) -> Option<(u32, u32)> {
+        if id >= self.get_tile_count() {
+            return None;
+        }
+        let columns = self.get_tileset_sprite_grid().columns;
+        let (column, row) = (id % columns, id / columns);
+        Some((
+            self.margin + column * (self.tile_size.0 + self.spacing),
+            self.margin + row * (self.tile_size.1 + self.spacing),
+        ))
+    }
+
+    /// Texture coordinates of a tile by its local ID, from 0 to 1 with the top of the image at 0.
+    /// IDs past the end of the tileset have none.
+    pub fn get_tile_coordinates(&self, id: u32) -> Option<TextureCoordinates> {
+        let (left, top) = self.get_tile_position(id)?;
+        let (width, height) = (self.tileset_size.0 as f32, self.tileset_size.1 as f32);
+        Some(TextureCoordinates {
+            left: left as f32 / width,
+            right: (left + self.tile_size.0) as f32 / width,
+            top: top as f32 / height,
+            bottom: (top + self.tile_size.1) as f32 / height,
+        })
+    }
+
+    /// How many tiles of `tile` pixels fit along `image` pixels of the image
+    fn fit(&self, image: u32, tile: u32) -> u32 {
+        // Spacing only sits between tiles, so there's one less of it than there are tiles
+        let usable = image.saturating_sub(self.margin * 2) + self.spacing;
+        usable / (tile + self.spacing).max(1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A tileset of 16 pixel tiles with nothing between them
+    fn tile_data(tileset_size: (u32, u32)) -> TileData {
+        TileData {
+            tile_size: (16, 16),
+            tileset_size,
+            margin: 0,
+            spacing: 0,
+            columns: None,
+            tile_count: None,
+        }
+    }
+
+    #[test]
+    fn margin_is_left_around_the_edges() {
+        let data = TileData {
+            margin: 2,
+            ..tile_data((36, 36))
+        };
+        assert_eq!(data.get_tileset_sprite_grid(), TileGrid { columns: 2, rows: 2 });
+        assert_eq!(data.get_tile_position(0), Some((2, 2)));
+        assert_eq!(data.get_tile_position(3), Some((18, 18)));
+
+        let coordinates = data.get_tile_coordinates(0).unwrap();
+        assert_eq!(coordinates.left, 2.0 / 36.0);
+        assert_eq!(coordinates.right, 18.0 / 36.0);
+        assert_eq!(coordinates.top, 2.0 / 36.0);
+        assert_eq!(coordinates.bottom, 18.0 / 36.0);
+    }
+
+    #[test]
+    fn spacing_is_left_between_tiles() {
+        let data = TileData {
+            spacing: 2,
+            ..tile_data((34, 34))
+        };
+        assert_eq!(data.get_tileset_sprite_grid(), TileGrid { columns: 2, rows: 2 });
+        assert_eq!(data.get_tile_position(1), Some((18, 0)));
+        assert_eq!(data.get_tile_position(3), Some((18, 18)));
+    }
+
+    #[test]
+    fn margin_and_spacing_together() {
+        let data = TileData {
+            margin: 1,
+            spacing: 1,
+            ..tile_data((52, 35))
+        };
+        assert_eq!(data.get_tileset_sprite_grid(), TileGrid { columns: 3, rows: 2 });
+        assert_eq!(data.get_tile_position(5), Some((35, 18)));
+    }
+
+    #[test]
+    fn explicit_columns_win_over_the_image_width() {
+        // The image has room for four columns, but Tiled only uses three
+        let data = TileData {
+            columns: Some(3),
+            ..tile_data((64, 32))
+        };
+        assert_eq!(data.get_tileset_sprite_grid(), TileGrid { columns: 3, rows: 2 });
+        assert_eq!(data.get_tile_count(), 6);
+        assert_eq!(data.get_tile_position(3), Some((0, 16)));
+    }
+
+    #[test]
+    fn tile_count_leaves_the_last_row_partly_empty() {
+        let data = TileData {
+            tile_count: Some(4),
+            ..tile_data((48, 32))
+        };
+        assert_eq!(data.get_tile_count(), 4);
+        assert_eq!(data.get_tile_position(3), Some((0, 16)));
+        assert_eq!(data.get_tile_position(4), None);
+        assert!(data.get_tile_coordinates(4).is_none());
+    }
+
+    #[test]
+    fn ids_past_the_end_have_no_tile() {
+        let data = tile_data((32, 32));
+        assert_eq!(data.get_tile_count(), 4);
+        assert!(data.get_tile_coordinates(3).is_some());
+        assert!(data.get_tile_coordinates(4).is_none());
+        assert!(data.get_tile_coordinates(u32::max_value()).is_none());
+    }
+
+    #[test]
+    fn tile_count_never_goes_past_the_image() {
+        let data = TileData {
+            tile_count: Some(10),
+            ..tile_data((32, 32))
+        };
+        assert_eq!(data.get_tile_count(), 4);
+        assert!(data.get_tile_coordinates(4).is_none());
+    }
+}
@@ -0,0 +1,231 @@
+//! Loading of TMX maps whose tilesets live in external TSX files. Tiled can't follow those
+//! references on its own when a map is read from memory, so we pull them out of the map, load
+//! each TSX through the asset source and put the parsed tilesets back in.
+use crate::util::path::{relative_path, resolve_relative};
+
+use amethyst::{
+    assets::{Format, FormatValue, Source},
+    error::Error,
+};
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex, MutexGuard},
+};
+use tiled::{Map, Tileset};
+use xml::{
+    reader::{EventReader, XmlEvent},
+    writer::EmitterConfig,
+};
+
+/// Parsed external tilesets by asset path. Every map using the same TSX shares one parse of it,
+/// for as long as the game runs or until the cache is cleared.
+#[derive(Clone, Debug, Default)]
+pub struct TilesetCache {
+    tilesets: Arc<Mutex<HashMap<String, Tileset>>>,
+}
+
+impl TilesetCache {
+    /// Forget every tileset, so they're read from disk again the next time a map uses them
+    pub fn clear(&self) -> Result<(), Error> {
+        self.lock()?.clear();
+        Ok(())
+    }
+
+    /// The tileset at the asset path `path`, with image paths relative to the TSX file
+    fn get_or_load(&self, path: &str, source: &dyn Source) -> Result<Tileset, Error> {
+        if let Some(tileset) = self.lock()?.get(path) {
+            return Ok(tileset.clone());
+        }
+        let bytes = source.load(path)?;
+        let tileset = tiled::parse_tileset(&bytes[..], 1).map_err(|error| {
+            Error::from_string(format!("Could not read tileset `{}`: {}", path, error))
+        })?;
+        self.lock()?.insert(path.to_string(), tileset.clone());
+        Ok(tileset)
+    }
+
+    /// Maps are loaded on the loader's worker threads, so a panic on one of them while holding
+    /// the lock has to turn into a load error on the others rather than another panic
+    fn lock(&self) -> Result<MutexGuard<HashMap<String, Tileset>>, Error> {
+        self.tilesets
+            .lock()
+            .map_err(|_| Error::from_string("The tileset cache was left broken by an earlier failed load".to_string()))
+    }
+}
+
+/// Reads TMX maps, with their tilesets either embedded or in external TSX files. Tile
+/// properties, animations and collision shapes come through the same either way.
+#[derive(Clone, Debug, Default)]
+pub struct MapFormat {
+    pub cache: TilesetCache,
+}
+
+impl MapFormat {
+    pub fn new(cache: TilesetCache) -> Self {
+        Self { cache }
+    }
+}
+
+impl Format<Map> for MapFormat {
+    fn name(&self) -> &'static str {
+        "TMX"
+    }
+
+    fn import(
+        &self,
+        name: String,
+        source: Arc<dyn Source>,
+        _create_reload: Option<Box<dyn Format<Map>>>,
+    ) -> Result<FormatValue<Map>, Error> {
+        let bytes = source.load(&name)?;
+
+        // Take the references out, leaving a map tiled can read from memory
+        let (embedded, references) = split_external_tilesets(&bytes)
+            .map_err(|error| Error::from_string(format!("Could not read map `{}`: {}", name, error)))?;
+        let mut map = tiled::parse(&embedded[..])
+            .map_err(|error| Error::from_string(format!("Could not read map `{}`: {}", name, error)))?;
+
+        for reference in references {
+            let path = resolve_relative(&name, &reference.source)
+                .map_err(|error| Error::from_string(error.to_string()))?;
+            let mut tileset = self.cache.get_or_load(&path, &*source)?;
+            tileset.first_gid = reference.first_gid;
+            // Image paths in a TSX are relative to it, but everything else expects them to be
+            // relative to the map
+            for image in tileset.images.iter_mut() {
+                let image_path = resolve_relative(&path, &image.source)
+                    .map_err(|error| Error::from_string(error.to_string()))?;
+                image.source = relative_path(&name, &image_path);
+            }
+            map.tilesets.push(tileset);
+        }
+        // Tiles are looked up by the tileset with the highest `first_gid` below them, and the
+        // textures are loaded in the same order, so keep them in the order Tiled wrote them
+        map.tilesets.sort_by_key(|tileset| tileset.first_gid);
+
+        Ok(FormatValue::data(map))
+    }
+}
+
+/// A `<tileset firstgid="..." source="..."/>` element in a TMX file
+#[derive(Clone, Debug, PartialEq)]
+struct TilesetReference {
+    first_gid: u32,
+    /// Path of the TSX file, relative to the map
+    source: String,
+}
+
+/// Split a TMX file into the same map without its external tileset elements, and those
+/// elements in the order they appear. Embedded tilesets are left where they are.
+fn split_external_tilesets(tmx: &[u8]) -> Result<(Vec<u8>, Vec<TilesetReference>), String> {
+    let mut embedded = Vec::new();
+    let mut writer = EmitterConfig::new().create_writer(&mut embedded);
+    let mut references = Vec::new();
+    // How deep into the document we are, with the `<map>` element at 1
+    let mut depth = 0;
+    // The depth of the external tileset we're leaving out, until we reach its end
+    let mut skipping = None;
+
+    for event in EventReader::new(tmx) {
+        let event = event.map_err(|error| error.to_string())?;
+        match &event {
+            XmlEvent::StartElement { name, attributes, .. } => {
+                depth += 1;
+                if skipping.is_some() {
+                    continue;
+                }
+                let attribute = |key: &str| {
+                    attributes
+                        .iter()
+                        .find(|attribute| attribute.name.local_name == key)
+                        .map(|attribute| attribute.value.clone())
+                };
+                // Tilesets directly inside the map are the only ones Tiled references by source
+                if depth == 2 && name.local_name == "tileset" {
+                    if let Some(source) = attribute("source") {
+                        let first_gid = attribute("firstgid").unwrap_or_default();
+                        let first_gid = first_gid.parse().map_err(|_| {
+                            format!("the tileset `{}` has an invalid firstgid `{}`", source, first_gid)
+                        })?;
+                        references.push(TilesetReference { first_gid, source });
+                        skipping = Some(depth);
+                        continue;
+                    }
+                }
+            }
+            XmlEvent::EndElement { .. } => {
+                let skipped = skipping.is_some();
+                if skipping == Some(depth) {
+                    skipping = None;
+                }
+                depth -= 1;
+                if skipped {
+                    continue;
+                }
+            }
+            _ => {
+                if skipping.is_some() {
+                    continue;
+                }
+            }
+        }
+        if let Some(event) = event.as_writer_event() {
+            writer.write(event).map_err(|error| error.to_string())?;
+        }
+    }
+
+    // The writer borrows the output until it's gone
+    drop(writer);
+    Ok((embedded, references))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn split(tmx: &str) -> (String, Vec<TilesetReference>) {
+        let (embedded, references) = split_external_tilesets(tmx.as_bytes()).unwrap();
+        (String::from_utf8(embedded).unwrap(), references)
+    }
+
+    fn reference(first_gid: u32, source: &str) -> TilesetReference {
+        TilesetReference {
+            first_gid,
+            source: source.to_string(),
+        }
+    }
+
+    #[test]
+    fn external_tilesets_are_taken_out() {
+        let (embedded, references) = split(
+            r#"<map version="1.2"><tileset firstgid="1" source="a.tsx"/><tileset firstgid="65" source="b.tsx"></tileset><layer name="ground"/></map>"#,
+        );
+        assert_eq!(references, vec![reference(1, "a.tsx"), reference(65, "b.tsx")]);
+        assert!(!embedded.contains("tileset"));
+        assert!(embedded.contains("ground"));
+    }
+
+    #[test]
+    fn attributes_can_be_written_any_way_xml_allows() {
+        let (_, references) = split(
+            "<map>\n <tileset\n\tfirstgid='12'\n\tsource='tiles/a&amp;b.tsx'/>\n</map>",
+        );
+        assert_eq!(references, vec![reference(12, "tiles/a&b.tsx")]);
+    }
+
+    #[test]
+    fn embedded_tilesets_and_comments_are_left_alone() {
+        let (embedded, references) = split(
+            r#"<map><!-- <tileset firstgid="3" source="old.tsx"/> --><tileset firstgid="1" name="inline"><image source="a.png"/></tileset></map>"#,
+        );
+        assert!(references.is_empty());
+        assert!(embedded.contains("inline"));
+        assert!(embedded.contains("a.png"));
+    }
+
+    #[test]
+    fn invalid_first_gid_is_an_error() {
+        assert!(split_external_tilesets(br#"<map><tileset firstgid="one" source="a.tsx"/></map>"#).is_err());
+        assert!(split_external_tilesets(br#"<map><tileset source="a.tsx"/></map>"#).is_err());
+    }
+}
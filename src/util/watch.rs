@@ -0,0 +1,61 @@
+//! Notices when files change on disk, so maps and configs can be reloaded while the game runs
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+/// Polls the modification time of a set of files. We only ever watch a handful of files, so
+/// polling is cheaper than it sounds and needs nothing from the platform.
+#[derive(Default)]
+pub struct FileWatcher {
+    files: HashMap<PathBuf, Option<SystemTime>>,
+}
+
+impl FileWatcher {
+    /// Start watching a file. Changes made before this are not reported. The file doesn't need
+    /// to exist yet, and creating it counts as a change.
+    pub fn watch(&mut self, path: PathBuf) {
+        let modified = modified(&path);
+        self.files.insert(path, modified);
+    }
+
+    pub fn unwatch(&mut self, path: &Path) {
+        self.files.remove(path);
+    }
+
+    /// Every watched file that changed since the last call
+    pub fn changed(&mut self) -> Vec<PathBuf> {
+        self.files
+            .iter_mut()
+            .filter_map(|(path, last_modified)| {
+                let modified = modified(path);
+                if modified == *last_modified {
+                    return None;
+                }
+                *last_modified = modified;
+                // A file that's gone, possibly only for a moment while an editor saves it, has
+                // nothing to reload
+                modified.map(|_| path.clone())
+            })
+            .collect()
+    }
+}
+
+/// Set by the running state so the hot reload system knows which map to watch, and by the hot
+/// reload system when that map changes on disk
+#[derive(Default)]
+pub struct HotReload {
+    /// Asset path of the current map
+    pub map: Option<String>,
+    /// Whether the current map changed since it was built. The running state rebuilds it and
+    /// clears this.
+    pub map_changed: bool,
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path)
+        .and_then(|metadata| metadata.modified())
+        .ok()
+}
@@ -0,0 +1,147 @@
+//! Loads maps through the game's own load states, without a window or renderer. The maps and
+//! everything they need are small fixtures under `tests/assets`, so these run without LFS.
+use amethyst::{
+    core::Transform,
+    ecs::{Builder, Join},
+    renderer::SpriteRender,
+};
+use j::{
+    components::{MapEntity, Player, TileChunk},
+    headless::Simulation,
+    states::LoadMapState,
+    util::{
+        chunk::{ChunkLayout, Rect, CHUNK_SIZE},
+        collision,
+        data::{PendingWarp, WarpTarget, MAP_ORIGIN},
+        flip::decode_gid,
+        layer::LayerSettings,
+        objects,
+    },
+};
+
+use std::{collections::HashSet, path::PathBuf, time::Duration};
+
+const FRAME: Duration = Duration::from_millis(16);
+
+fn assets_dir() -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/assets")
+}
+
+/// Start the game on `map` and step until the player has been built
+fn load(map: &str) -> Simulation<'static, 'static> {
+    let mut simulation = Simulation::new(&assets_dir()).expect("Could not set up the simulation");
+    simulation
+        .start(LoadMapState::new(map, None))
+        .expect("Could not start loading");
+    let loaded = simulation.step_until(600, FRAME, |world| {
+        (&world.read_storage::<Player>()).join().next().is_some()
+    });
+    assert!(loaded, "`{}` never finished loading", map);
+    simulation
+}
+
+#[test]
+fn player_starts_at_the_spawn_point() {
+    let simulation = load("maps/small.tmx");
+    let map = tiled::parse_file(&assets_dir().join("maps/small.tmx")).unwrap();
+    let spawn = objects::player_spawn(&map, None).expect("The fixture map should have a spawn point");
+
+    let transforms = simulation.world.read_storage::<Transform>();
+    let players = simulation.world.read_storage::<Player>();
+    let (transform, _) = (&transforms, &players).join().next().unwrap();
+    assert_eq!(transform.translation().x, spawn.x);
+    assert_eq!(transform.translation().y, spawn.y);
+}
+
+#[test]
+fn tiles_are_drawn_a_chunk_at_a_time() {
+    let simulation = load("maps/small.tmx");
+    let map = tiled::parse_file(&assets_dir().join("maps/small.tmx")).unwrap();
+    let layout = ChunkLayout {
+        chunk_size: CHUNK_SIZE,
+        tile_size: (map.tile_width as f32, map.tile_height as f32),
+        origin: MAP_ORIGIN,
+    };
+
+    // Every chunk of a still layer with any tiles in it, by where its middle is
+    let mut expected = Vec::new();
+    for layer in map.layers.iter().filter(|layer| !collision::is_collision_layer(layer)) {
+        let settings = LayerSettings::from_layer(layer);
+        if !settings.visible || settings.has_parallax() {
+            continue;
+        }
+        let chunks: HashSet<(usize, usize)> = layer
+            .tiles
+            .iter()
+            .enumerate()
+            .flat_map(|(row, tiles)| {
+                tiles
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, &gid)| decode_gid(gid).0 != 0)
+                    .map(move |(column, _)| (column, row))
+            })
+            .map(|(column, row)| layout.chunk_of(column, row))
+            .collect();
+        for chunk in chunks {
+            let bounds = layout.bounds(chunk).translate(settings.offset.0, settings.offset.1);
+            expected.push(bounds);
+        }
+    }
+    // Both of the fixture's layers have tiles either side of column 16, so two chunks each
+    assert_eq!(expected.len(), 4);
+
+    let world = &simulation.world;
+    let chunks: Vec<(Rect, (f32, f32))> = (
+        &world.read_storage::<TileChunk>(),
+        &world.read_storage::<Transform>(),
+    )
+        .join()
+        .map(|(chunk, transform)| {
+            let translation = transform.translation();
+            (chunk.bounds, (translation.x.as_f32(), translation.y.as_f32()))
+        })
+        .collect();
+    // One entity for each chunk of each layer, rather than one for each tile
+    assert_eq!(chunks.len(), expected.len());
+    for bounds in expected.iter() {
+        let (_, position) = chunks
+            .iter()
+            .find(|(chunk_bounds, _)| chunk_bounds == bounds)
+            .unwrap_or_else(|| panic!("No chunk covering {:?}", bounds));
+        assert_eq!(
+            *position,
+            ((bounds.left + bounds.right) / 2.0, (bounds.bottom + bounds.top) / 2.0),
+        );
+    }
+
+    // Tiles stay sprites on their tileset's sheet, grouped under their chunk
+    let sprites = world.read_storage::<SpriteRender>();
+    for chunk in (&world.read_storage::<TileChunk>()).join() {
+        assert!(!chunk.tiles.is_empty());
+        assert!(chunk.tiles.iter().all(|&tile| sprites.get(tile).is_some()));
+    }
+}
+
+#[test]
+fn warping_only_tears_down_what_the_map_built() {
+    let mut simulation = load("maps/small.tmx");
+    let player = {
+        let entities = simulation.world.entities();
+        let players = simulation.world.read_storage::<Player>();
+        let (player, _) = (&entities, &players).join().next().unwrap();
+        player
+    };
+    // Something another state made, which has nothing to do with the map
+    let unrelated = simulation.world.create_entity().build();
+
+    simulation.world.write_resource::<PendingWarp>().0 = Some(WarpTarget {
+        map: "small.tmx".to_string(),
+        spawn: "start".to_string(),
+    });
+    simulation.step(1, FRAME);
+
+    assert!(simulation.world.is_alive(unrelated));
+    assert!(!simulation.world.is_alive(player));
+    assert_eq!((&simulation.world.read_storage::<MapEntity>()).join().count(), 0);
+}
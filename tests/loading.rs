@@ -0,0 +1,247 @@
+//! Assets that are missing or broken end up as load errors, instead of hanging or panicking
+use amethyst::{
+    assets::{AssetStorage, Format, Handle, Loader, ProgressCounter, RonFormat},
+    renderer::{formats::texture::ImageFormat, sprite::SpriteSheet, Texture},
+    GameData, State, StateEvent,
+};
+use j::{
+    headless::Simulation,
+    states::{LoadDataState, LoadMapState, MainGameState},
+    util::{
+        data::{CameraConfig, PlayerConfig},
+        loading::{load_status, LoadDeadline, LoadError, LoadFailure, LoadStatus},
+        manifest::{AssetEntry, AssetHandles, AssetLoaders, AssetManifest},
+        tmx::MapFormat,
+    },
+};
+
+use std::{collections::HashMap, path::PathBuf, time::Duration};
+use tiled::Map;
+
+const FRAME: Duration = Duration::from_millis(16);
+
+fn simulation() -> Simulation<'static, 'static> {
+    let assets_dir = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/assets");
+    Simulation::new(&assets_dir).expect("Could not set up the simulation")
+}
+
+/// Load `path` from the test assets and step until it either loads or fails
+fn load<A, F>(path: &str, format: F) -> LoadStatus
+where
+    A: amethyst::assets::Asset,
+    F: Format<A::Data>,
+{
+    let mut simulation = simulation();
+    let mut progress = ProgressCounter::new();
+    simulation.world.read_resource::<Loader>().load(
+        path,
+        format,
+        &mut progress,
+        &simulation.world.read_resource::<AssetStorage<A>>(),
+    );
+    simulation.step_until(600, FRAME, |_| match load_status(&[&progress]) {
+        LoadStatus::Loading => false,
+        _ => true,
+    });
+    load_status(&[&progress])
+}
+
+fn errors(status: LoadStatus) -> Vec<LoadError> {
+    match status {
+        LoadStatus::Failed(errors) => errors,
+        status => panic!("Expected the load to fail, but it's {:?}", status),
+    }
+}
+
+#[test]
+fn missing_map_fails_to_load() {
+    let errors = errors(load::<Map, _>("maps/missing.tmx", MapFormat::default()));
+    assert_eq!(errors.len(), 1);
+    assert_eq!(errors[0].asset, "maps/missing.tmx");
+}
+
+#[test]
+fn corrupt_map_fails_to_load() {
+    let errors = errors(load::<Map, _>("maps/corrupt.tmx", MapFormat::default()));
+    assert_eq!(errors.len(), 1);
+    assert_eq!(errors[0].asset, "maps/corrupt.tmx");
+    assert!(!errors[0].reason.is_empty());
+}
+
+#[test]
+fn missing_config_fails_to_load() {
+    let errors = errors(load::<CameraConfig, _>("config/missing.ron", RonFormat));
+    assert_eq!(errors[0].asset, "config/missing.ron");
+}
+
+#[test]
+fn corrupt_config_fails_to_load() {
+    let errors = errors(load::<CameraConfig, _>("config/corrupt.ron", RonFormat));
+    assert_eq!(errors.len(), 1);
+    assert_eq!(errors[0].asset, "config/corrupt.ron");
+}
+
+fn entry(path: &str, kind: &str) -> AssetEntry {
+    AssetEntry {
+        path: path.to_string(),
+        kind: kind.to_string(),
+        options: HashMap::new(),
+    }
+}
+
+#[test]
+fn manifest_entries_load_through_the_loader_of_their_kind() {
+    let mut simulation = simulation();
+    let mut manifest = AssetManifest::default();
+    manifest.assets.insert("camera".to_string(), entry("config/camera.ron", "Camera"));
+    manifest.assets.insert("unknown".to_string(), entry("config/camera.ron", "Unknown"));
+    manifest.assets.insert("sprites".to_string(), entry("textures/missing.png.ron", "SpriteSheet"));
+    let loaders = AssetLoaders::default().with_ron::<CameraConfig>("Camera");
+
+    let mut progress = ProgressCounter::new();
+    let mut textures = ProgressCounter::new();
+    let mut errors = Vec::new();
+    let handles = manifest.load(&simulation.world, &loaders, &mut progress, &mut textures, &mut errors);
+
+    // Kinds nobody registered, and entries missing an option their loader needs, are reported
+    // without stopping the rest
+    let mut failed: Vec<&str> = errors.iter().map(|error| error.asset.as_str()).collect();
+    failed.sort();
+    assert_eq!(failed, vec!["sprites", "unknown"]);
+    assert!(handles.get::<SpriteSheet>("sprites").is_none());
+
+    // Handles come back out with the type their loader gave them
+    assert!(handles.get::<PlayerConfig>("camera").is_none());
+    let camera = handles.get::<CameraConfig>("camera").expect("The camera config has no handle");
+    let loaded = simulation.step_until(600, FRAME, |_| match load_status(&[&progress]) {
+        LoadStatus::Loaded => true,
+        _ => false,
+    });
+    assert!(loaded, "The camera config never finished loading");
+    assert!(simulation.world.read_resource::<AssetStorage<CameraConfig>>().get(&camera).is_some());
+}
+
+/// Start `state` and step until it gives up and switches to the `ErrorState`, returning why
+fn failure<S>(simulation: &mut Simulation<'static, 'static>, state: S) -> Vec<LoadError>
+where
+    S: State<GameData<'static, 'static>, StateEvent> + 'static,
+{
+    simulation.start(state).expect("Could not start the state");
+    let failed = simulation.step_until(600, FRAME, |world| world.res.has_value::<LoadFailure>());
+    assert!(failed, "Never switched to the error state");
+    simulation.world.read_resource::<LoadFailure>().errors.clone()
+}
+
+/// Load the fixture map ourselves, for states that expect it to be loaded already
+fn loaded_map(simulation: &mut Simulation<'static, 'static>) -> Handle<Map> {
+    let mut progress = ProgressCounter::new();
+    let map = simulation.world.read_resource::<Loader>().load(
+        "maps/small.tmx",
+        MapFormat::default(),
+        &mut progress,
+        &simulation.world.read_resource::<AssetStorage<Map>>(),
+    );
+    let loaded = simulation.step_until(600, FRAME, |_| progress.is_complete());
+    assert!(loaded, "The fixture map never finished loading");
+    map
+}
+
+#[test]
+fn missing_map_switches_to_the_error_state() {
+    let mut simulation = simulation();
+    let errors = failure(&mut simulation, LoadMapState::new("maps/missing.tmx", None));
+    assert_eq!(errors.len(), 1);
+    assert_eq!(errors[0].asset, "maps/missing.tmx");
+}
+
+#[test]
+fn corrupt_map_switches_to_the_error_state() {
+    let mut simulation = simulation();
+    let errors = failure(&mut simulation, LoadMapState::new("maps/corrupt.tmx", None));
+    assert_eq!(errors.len(), 1);
+    assert_eq!(errors[0].asset, "maps/corrupt.tmx");
+}
+
+#[test]
+fn corrupt_config_switches_to_the_error_state() {
+    let mut simulation = simulation();
+    let map_handle = loaded_map(&mut simulation);
+    let mut manifest = AssetManifest::default();
+    manifest
+        .assets
+        .insert("camera_config".to_string(), entry("config/corrupt.ron", "CameraConfig"));
+    let manifest_handle = simulation.world.read_resource::<Loader>().load_from_data(
+        manifest,
+        (),
+        &simulation.world.read_resource::<AssetStorage<AssetManifest>>(),
+    );
+    // The manifest is processed along with everything else
+    simulation.step(1, FRAME);
+
+    let errors = failure(
+        &mut simulation,
+        LoadDataState {
+            map_path: "maps/small.tmx".to_string(),
+            map_handle: Some(map_handle),
+            manifest_handle: Some(manifest_handle),
+            ..LoadDataState::default()
+        },
+    );
+    assert_eq!(errors.len(), 1);
+    assert_eq!(errors[0].asset, "config/corrupt.ron");
+}
+
+#[test]
+fn loads_that_never_finish_switch_to_the_error_state() {
+    let mut simulation = simulation();
+    let map_handle = loaded_map(&mut simulation);
+    // As if an earlier map had loaded the manifest already
+    simulation.world.add_resource(AssetHandles::default());
+    let mut state = LoadDataState {
+        map_path: "maps/small.tmx".to_string(),
+        map_handle: Some(map_handle),
+        deadline: LoadDeadline::new(Duration::from_secs(1)),
+        ..LoadDataState::default()
+    };
+    // Nothing processes textures without a renderer, so this never finishes
+    simulation.world.read_resource::<Loader>().load(
+        "textures/tiles.png",
+        ImageFormat::default(),
+        &mut state.progress,
+        &simulation.world.read_resource::<AssetStorage<Texture>>(),
+    );
+
+    let errors = failure(&mut simulation, state);
+    assert_eq!(errors.len(), 1);
+    assert_eq!(errors[0].asset, "assets for `maps/small.tmx`");
+    assert_eq!(errors[0].reason, "still loading after 1 seconds");
+}
+
+#[test]
+fn missing_required_assets_switch_to_the_error_state() {
+    let mut simulation = simulation();
+    let map_handle = loaded_map(&mut simulation);
+    let map = simulation
+        .world
+        .read_resource::<AssetStorage<Map>>()
+        .get(&map_handle)
+        .cloned()
+        .unwrap();
+
+    // Nothing from the manifest was ever loaded, so there's no player or camera to build
+    let errors = failure(
+        &mut simulation,
+        MainGameState {
+            map_path: "maps/small.tmx".to_string(),
+            spawn: None,
+            map,
+            tilesets: Vec::new(),
+            reloading: None,
+            failed: None,
+            player: None,
+            camera: None,
+        },
+    );
+    assert_eq!(errors.len(), 1);
+    assert_eq!(errors[0].asset, "config/assets.ron");
+}